@@ -0,0 +1,59 @@
+use solana_sdk::pubkey::Pubkey;
+use std::{
+    collections::HashMap,
+    time::{Duration, Instant},
+};
+use tokio::sync::{Mutex, MutexGuard};
+
+// How long a requested airdrop counts towards an address's balance. Faucet
+// airdrops land well within this, after which getBalance reflects them.
+const PENDING_AIRDROP_TTL: Duration = Duration::from_secs(60);
+
+// Airdrops that have been requested but may not show up in getBalance yet.
+// Counting them keeps concurrent `minBalance` requests for the same address
+// from funding it twice.
+#[derive(Default)]
+pub struct AirdropTracker {
+    pending: Mutex<HashMap<Pubkey, (u64, Instant)>>,
+}
+
+impl AirdropTracker {
+    // Airdrops are rare, so one lock held across the balance check and the
+    // airdrop request is simpler than per-address locking.
+    pub async fn lock(&self) -> PendingAirdrops<'_> {
+        PendingAirdrops(self.pending.lock().await)
+    }
+}
+
+pub struct PendingAirdrops<'a>(MutexGuard<'a, HashMap<Pubkey, (u64, Instant)>>);
+
+impl PendingAirdrops<'_> {
+    pub fn pending(&mut self, address: &Pubkey) -> u64 {
+        self.0.retain(|_, (_, requested_at)| requested_at.elapsed() < PENDING_AIRDROP_TTL);
+        self.0.get(address).map_or(0, |(lamports, _)| *lamports)
+    }
+
+    pub fn record(&mut self, address: Pubkey, lamports: u64) {
+        let entry = self.0.entry(address).or_insert((0, Instant::now()));
+        entry.0 = entry.0.saturating_add(lamports);
+        entry.1 = Instant::now();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn recorded_airdrops_count_as_pending() {
+        let tracker = AirdropTracker::default();
+        let address = Pubkey::new_unique();
+
+        let mut pending = tracker.lock().await;
+        assert_eq!(pending.pending(&address), 0);
+        pending.record(address, 100);
+        pending.record(address, 50);
+        assert_eq!(pending.pending(&address), 150);
+        assert_eq!(pending.pending(&Pubkey::new_unique()), 0);
+    }
+}
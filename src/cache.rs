@@ -0,0 +1,187 @@
+use lru::LruCache;
+use rand::Rng;
+use solana_sdk::{commitment_config::CommitmentLevel, hash::Hash, pubkey::Pubkey};
+use std::{
+    future::Future,
+    num::NonZeroUsize,
+    sync::Mutex,
+    time::{Duration, Instant},
+};
+
+// Upper bound on distinct (address, commitment) pairs kept in memory. Beyond
+// this the least recently read entry is dropped.
+const BALANCE_CACHE_CAPACITY: usize = 10_000;
+
+type BalanceKey = (Pubkey, CommitmentLevel);
+
+pub struct BalanceCache {
+    ttl: Duration,
+    entries: Mutex<LruCache<BalanceKey, (u64, Instant)>>,
+}
+
+impl BalanceCache {
+    pub fn new(ttl: Duration) -> Self {
+        Self {
+            ttl,
+            entries: Mutex::new(LruCache::new(
+                NonZeroUsize::new(BALANCE_CACHE_CAPACITY).unwrap(),
+            )),
+        }
+    }
+
+    pub fn ttl(&self) -> Duration {
+        self.ttl
+    }
+
+    // Returns the cached lamports and the age of the entry, dropping it if
+    // it has outlived the TTL.
+    pub fn get(&self, key: &BalanceKey) -> Option<(u64, Duration)> {
+        let mut entries = self.entries.lock().unwrap();
+        let (lamports, fetched_at) = *entries.get(key)?;
+        let age = fetched_at.elapsed();
+        if age >= self.ttl {
+            entries.pop(key);
+            return None;
+        }
+        Some((lamports, age))
+    }
+
+    pub fn insert(&self, key: BalanceKey, lamports: u64) {
+        self.entries
+            .lock()
+            .unwrap()
+            .put(key, (lamports, Instant::now()));
+    }
+}
+
+// Each cached blockhash expires somewhere between 80% and 100% of the TTL,
+// so instances behind a load balancer don't all refetch at the same moment.
+// Jitter only ever shortens the TTL, which keeps the freshness bound.
+const BLOCKHASH_TTL_JITTER: f64 = 0.2;
+
+struct CachedBlockhash {
+    hash: Hash,
+    last_valid_block_height: u64,
+    fetched_at: Instant,
+    expires_after: Duration,
+}
+
+pub struct BlockhashCache {
+    ttl: Duration,
+    // A tokio mutex, held across the fetch, so a burst of misses makes one
+    // RPC call instead of one each.
+    entry: tokio::sync::Mutex<Option<CachedBlockhash>>,
+}
+
+impl BlockhashCache {
+    pub fn new(ttl: Duration) -> Self {
+        Self {
+            ttl,
+            entry: tokio::sync::Mutex::new(None),
+        }
+    }
+
+    pub fn ttl(&self) -> Duration {
+        self.ttl
+    }
+
+    // Returns the blockhash, its last valid block height, and the age of the
+    // cached entry (None when it was just fetched).
+    pub async fn get_or_fetch<F, Fut, E>(&self, fetch: F) -> Result<(Hash, u64, Option<Duration>), E>
+    where
+        F: FnOnce() -> Fut,
+        Fut: Future<Output = Result<(Hash, u64), E>>,
+    {
+        let mut entry = self.entry.lock().await;
+        if let Some(cached) = entry.as_ref() {
+            let age = cached.fetched_at.elapsed();
+            if age < cached.expires_after {
+                return Ok((cached.hash, cached.last_valid_block_height, Some(age)));
+            }
+        }
+
+        let (hash, last_valid_block_height) = fetch().await?;
+        let jitter = rand::thread_rng().gen_range(0.0..=BLOCKHASH_TTL_JITTER);
+        *entry = Some(CachedBlockhash {
+            hash,
+            last_valid_block_height,
+            fetched_at: Instant::now(),
+            expires_after: self.ttl.mul_f64(1.0 - jitter),
+        });
+        Ok((hash, last_valid_block_height, None))
+    }
+}
+
+// How long the minimum stake delegation is reused. It only changes when a
+// feature gate activates at an epoch boundary, so a minute-old value is
+// almost always current.
+pub const STAKE_MINIMUM_TTL: Duration = Duration::from_secs(60);
+
+// How long the SOL supply is reused. It moves with inflation and burns, which
+// dashboards don't need to the second.
+pub const SUPPLY_TTL: Duration = Duration::from_secs(30);
+
+// A single slowly-changing cluster value, reused for a fixed TTL.
+pub struct ValueCache<T> {
+    ttl: Duration,
+    entry: tokio::sync::Mutex<Option<(T, Instant)>>,
+}
+
+impl<T: Clone> ValueCache<T> {
+    pub fn new(ttl: Duration) -> Self {
+        Self {
+            ttl,
+            entry: tokio::sync::Mutex::new(None),
+        }
+    }
+
+    // Returns the value and the age of the cached entry (None when it was
+    // just fetched). The lock is held across the fetch, as with the
+    // blockhash cache.
+    pub async fn get_or_fetch<F, Fut, E>(&self, fetch: F) -> Result<(T, Option<Duration>), E>
+    where
+        F: FnOnce() -> Fut,
+        Fut: Future<Output = Result<T, E>>,
+    {
+        let mut entry = self.entry.lock().await;
+        if let Some((value, fetched_at)) = entry.as_ref() {
+            let age = fetched_at.elapsed();
+            if age < self.ttl {
+                return Ok((value.clone(), Some(age)));
+            }
+        }
+
+        let value = fetch().await?;
+        *entry = Some((value.clone(), Instant::now()));
+        Ok((value, None))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::atomic::{AtomicUsize, Ordering};
+
+    #[tokio::test]
+    async fn blockhash_cache_serves_within_ttl_and_refetches_after() {
+        let cache = BlockhashCache::new(Duration::from_millis(50));
+        let fetches = AtomicUsize::new(0);
+        let fetch = || async {
+            fetches.fetch_add(1, Ordering::SeqCst);
+            Ok::<_, ()>((Hash::new_unique(), 100))
+        };
+
+        let (first, _, age) = cache.get_or_fetch(fetch).await.unwrap();
+        assert!(age.is_none());
+        let (second, _, age) = cache.get_or_fetch(fetch).await.unwrap();
+        assert_eq!(first, second);
+        assert!(age.is_some());
+        assert_eq!(fetches.load(Ordering::SeqCst), 1);
+
+        tokio::time::sleep(Duration::from_millis(60)).await;
+        let (third, _, age) = cache.get_or_fetch(fetch).await.unwrap();
+        assert_ne!(first, third);
+        assert!(age.is_none());
+        assert_eq!(fetches.load(Ordering::SeqCst), 2);
+    }
+}
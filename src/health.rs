@@ -0,0 +1,214 @@
+use async_trait::async_trait;
+use serde::Serialize;
+use solana_client::nonblocking::rpc_client::RpcClient;
+use std::{
+    sync::Arc,
+    time::{Duration, Instant},
+};
+use utoipa::ToSchema;
+
+// A check that hangs is reported as down rather than holding /ready open.
+const CHECK_TIMEOUT: Duration = Duration::from_secs(2);
+
+// Something the server needs to be useful. New dependencies implement this
+// and get registered in `ReadinessChecks::new`.
+#[async_trait]
+pub trait DependencyCheck: Send + Sync {
+    async fn check(&self) -> Result<(), String>;
+}
+
+struct RpcHealth(Arc<RpcClient>);
+
+#[async_trait]
+impl DependencyCheck for RpcHealth {
+    async fn check(&self) -> Result<(), String> {
+        self.0.get_health().await.map_err(|e| e.to_string())
+    }
+}
+
+#[derive(Clone)]
+struct Dependency {
+    name: &'static str,
+    // Optional dependencies are reported but never make the server not-ready.
+    required: bool,
+    check: Arc<dyn DependencyCheck>,
+}
+
+#[derive(Debug, Serialize, ToSchema)]
+pub struct DependencyStatus {
+    name: String,
+    required: bool,
+    healthy: bool,
+    latency_ms: u64,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    error: Option<String>,
+}
+
+impl DependencyStatus {
+    // Whether this result should make the server report not-ready.
+    pub fn is_blocking(&self) -> bool {
+        self.required && !self.healthy
+    }
+}
+
+#[derive(Clone, Default)]
+pub struct ReadinessChecks {
+    dependencies: Vec<Dependency>,
+}
+
+impl ReadinessChecks {
+    // Every known dependency, all required. The write client only gets its
+    // own check when it is a separate node from the read client.
+    pub fn new(rpc: &Arc<RpcClient>, write_rpc: &Arc<RpcClient>) -> Self {
+        let mut checks = Self::default();
+        checks.register("rpc", RpcHealth(rpc.clone()));
+        if !Arc::ptr_eq(rpc, write_rpc) {
+            checks.register("rpc_write", RpcHealth(write_rpc.clone()));
+        }
+        checks
+    }
+
+    // Narrows the checks to READY_CHECKS (when set) and marks the ones in
+    // READY_OPTIONAL_CHECKS as optional. Unknown names are an error, since a
+    // typo would otherwise silently drop a check.
+    pub fn from_env(rpc: &Arc<RpcClient>, write_rpc: &Arc<RpcClient>) -> Result<Self, String> {
+        let mut checks = Self::new(rpc, write_rpc);
+        if let Some(names) = env_list("READY_CHECKS") {
+            checks.ensure_known(&names)?;
+            checks.dependencies.retain(|d| names.iter().any(|n| n == d.name));
+        }
+        if let Some(names) = env_list("READY_OPTIONAL_CHECKS") {
+            checks.ensure_known(&names)?;
+            for dependency in &mut checks.dependencies {
+                if names.iter().any(|n| n == dependency.name) {
+                    dependency.required = false;
+                }
+            }
+        }
+        Ok(checks)
+    }
+
+    pub fn names(&self) -> Vec<String> {
+        self.dependencies.iter().map(|d| d.name.to_string()).collect()
+    }
+
+    pub fn register(&mut self, name: &'static str, check: impl DependencyCheck + 'static) {
+        self.dependencies.push(Dependency {
+            name,
+            required: true,
+            check: Arc::new(check),
+        });
+    }
+
+    // Runs every check concurrently, reporting results in registration order.
+    pub async fn run(&self) -> Vec<DependencyStatus> {
+        let handles = self
+            .dependencies
+            .iter()
+            .cloned()
+            .map(|dependency| {
+                let (name, required) = (dependency.name, dependency.required);
+                let started = Instant::now();
+                let handle = tokio::spawn(async move {
+                    let result = match tokio::time::timeout(CHECK_TIMEOUT, dependency.check.check()).await {
+                        Ok(result) => result,
+                        Err(_) => Err(format!("Timed out after {}ms", CHECK_TIMEOUT.as_millis())),
+                    };
+                    DependencyStatus {
+                        name: dependency.name.to_string(),
+                        required: dependency.required,
+                        healthy: result.is_ok(),
+                        latency_ms: started.elapsed().as_millis() as u64,
+                        error: result.err(),
+                    }
+                });
+                (name, required, started, handle)
+            })
+            .collect::<Vec<_>>();
+
+        let mut statuses = Vec::with_capacity(handles.len());
+        for (name, required, started, handle) in handles {
+            statuses.push(match handle.await {
+                Ok(status) => status,
+                // A check that panicked is down, not absent.
+                Err(e) => DependencyStatus {
+                    name: name.to_string(),
+                    required,
+                    healthy: false,
+                    latency_ms: started.elapsed().as_millis() as u64,
+                    error: Some(e.to_string()),
+                },
+            });
+        }
+        statuses
+    }
+
+    fn ensure_known(&self, names: &[String]) -> Result<(), String> {
+        match names.iter().find(|n| !self.dependencies.iter().any(|d| d.name == n.as_str())) {
+            Some(unknown) => Err(format!("Unknown readiness check {:?}", unknown)),
+            None => Ok(()),
+        }
+    }
+}
+
+fn env_list(name: &str) -> Option<Vec<String>> {
+    std::env::var(name).ok().map(|v| {
+        v.split(',')
+            .map(|s| s.trim().to_string())
+            .filter(|s| !s.is_empty())
+            .collect()
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    struct Fixed(Result<(), &'static str>);
+
+    #[async_trait]
+    impl DependencyCheck for Fixed {
+        async fn check(&self) -> Result<(), String> {
+            self.0.map_err(str::to_string)
+        }
+    }
+
+    struct Panics;
+
+    #[async_trait]
+    impl DependencyCheck for Panics {
+        async fn check(&self) -> Result<(), String> {
+            panic!("check panicked")
+        }
+    }
+
+    fn blocking(statuses: &[DependencyStatus]) -> Vec<&str> {
+        statuses.iter().filter(|s| s.is_blocking()).map(|s| s.name.as_str()).collect()
+    }
+
+    #[tokio::test]
+    async fn only_required_failures_block_readiness() {
+        let mut checks = ReadinessChecks::default();
+        checks.register("up", Fixed(Ok(())));
+        checks.register("optional", Fixed(Err("unreachable")));
+        checks.dependencies[1].required = false;
+        let statuses = checks.run().await;
+        assert_eq!(statuses.iter().map(|s| s.name.as_str()).collect::<Vec<_>>(), ["up", "optional"]);
+        assert_eq!(statuses[1].error.as_deref(), Some("unreachable"));
+        assert!(blocking(&statuses).is_empty());
+
+        checks.register("down", Fixed(Err("unreachable")));
+        assert_eq!(blocking(&checks.run().await), ["down"]);
+    }
+
+    #[tokio::test]
+    async fn a_panicking_check_is_reported_down() {
+        let mut checks = ReadinessChecks::default();
+        checks.register("panics", Panics);
+        let statuses = checks.run().await;
+        assert_eq!(statuses.len(), 1);
+        assert!(!statuses[0].healthy);
+        assert!(statuses[0].error.as_deref().unwrap().contains("panicked"));
+        assert_eq!(blocking(&statuses), ["panics"]);
+    }
+}
@@ -1,7 +1,9 @@
 use axum::{
-    extract::{FromRequest, Json, Request},
+    body::{Body, Bytes},
+    extract::{rejection::JsonRejection, FromRequest, Json, Request},
     http::StatusCode,
 };
+use crate::routes::{error_body, EnvelopeVersion};
 use serde::de::DeserializeOwned;
 
 // We'll take a simpler approach: a wrapper around the existing Json extractor
@@ -13,19 +15,287 @@ pub async fn extract_json_with_error_status<T>(
 where
     T: DeserializeOwned,
 {
-    match Json::<T>::from_request(req, &()).await {
+    // Buffered up front so a syntax error can be explained from the bytes
+    // around it; Json then reads the same bytes.
+    let (parts, body) = req.into_parts();
+    let bytes = match Bytes::from_request(Request::from_parts(parts.clone(), body), &()).await {
+        Ok(bytes) => bytes,
+        Err(rejection) => return Err(rejection_response(rejection.status(), None)),
+    };
+    match Json::<T>::from_request(Request::from_parts(parts, Body::from(bytes.clone())), &()).await {
         Ok(json) => Ok(json),
-        Err(_rejection) => {
+        Err(rejection) => Err(rejection_response(rejection.status(), numeric_error(&rejection, &bytes))),
+    }
+}
+
+fn rejection_response(status: StatusCode, error: Option<String>) -> (StatusCode, axum::Json<serde_json::Value>) {
+    match status {
+        // A gzip body is checked against the limit after inflating, so this
+        // is also what a decompression bomb gets.
+        StatusCode::PAYLOAD_TOO_LARGE => (
+            StatusCode::PAYLOAD_TOO_LARGE,
+            axum::Json(error_body("Request body is too large".to_string(), None, Default::default())),
+        ),
+        _ => {
             // Return a 400 Bad Request with a JSON error body
             // Make sure it matches the expected error format by tests
-            Err((
-                StatusCode::BAD_REQUEST,
-                axum::Json(serde_json::json!({
+            let error = error.unwrap_or_else(|| "Invalid or missing field in JSON request body".to_string());
+            let body = match EnvelopeVersion::current() {
+                EnvelopeVersion::V1 => serde_json::json!({
                     "success": false,
-                    "error": "Invalid or missing field in JSON request body",
+                    "error": error,
                     "data": null
-                }))
-            ))
+                }),
+                EnvelopeVersion::V2 => error_body(error, None, Default::default()),
+            };
+            (StatusCode::BAD_REQUEST, axum::Json(body))
+        }
+    }
+}
+
+const INTEGER_TYPES: [&str; 8] = ["u8", "u16", "u32", "u64", "i8", "i16", "i32", "i64"];
+
+// Names the field when a number doesn't fit its integer type, e.g.
+// "amount: number out of range for u64". serde_json reads integers too big
+// for u64 as floats, so those show up as a type error rather than a value one.
+//
+// Amounts are money, so anything that could be read at the wrong magnitude
+// is refused with its own message rather than coerced: exponents (`1e9`),
+// decimal points on whole values, quoted numbers, leading zeros, and
+// thousands separators.
+fn numeric_error(rejection: &JsonRejection, body: &[u8]) -> Option<String> {
+    let e = match rejection {
+        JsonRejection::JsonDataError(e) => e as &dyn std::error::Error,
+        JsonRejection::JsonSyntaxError(e) => e,
+        _ => return None,
+    };
+    let mut source = e.source();
+    let error = loop {
+        let current = source?;
+        if let Some(error) = current.downcast_ref::<serde_path_to_error::Error<serde_json::Error>>() {
+            break error;
         }
+        source = current.source();
+    };
+
+    let message = error.inner().to_string();
+    // Leading zeros and thousands separators are syntax errors, caught
+    // before any field is known, so only the position can be given.
+    if error.inner().is_syntax() {
+        return syntax_error(error.inner(), body);
+    }
+
+    let expected = message.split("expected ").nth(1)?.split(' ').next()?;
+    let expected = INTEGER_TYPES.iter().find(|ty| **ty == expected)?;
+    let reason = if message.starts_with("invalid value: integer") {
+        format!("number out of range for {}", expected)
+    } else if message.starts_with("invalid type: floating point") {
+        let (min, max) = integer_range(expected);
+        match message.split('`').nth(1)?.parse::<f64>() {
+            Ok(value) if value.fract() != 0.0 => format!("expected a whole number for {}", expected),
+            Ok(value) if value >= min && value < max => format!(
+                "write {} values as plain integers, without an exponent or decimal point",
+                expected
+            ),
+            Ok(_) => format!("number out of range for {}", expected),
+            Err(_) => format!("expected a whole number for {}", expected),
+        }
+    } else if message.starts_with("invalid type: string") {
+        format!("expected a JSON number for {}, not a string", expected)
+    } else {
+        return None;
+    };
+    Some(format!("{}: {}", error.path(), reason))
+}
+
+fn syntax_error(error: &serde_json::Error, body: &[u8]) -> Option<String> {
+    let position = format!("line {} column {}", error.line(), error.column());
+    // serde_json points at the byte it couldn't accept.
+    let line_start: usize = body
+        .split(|b| *b == b'\n')
+        .take(error.line().saturating_sub(1))
+        .map(|line| line.len() + 1)
+        .sum();
+    let offset = line_start + error.column().saturating_sub(1);
+    let at = |i: usize| body.get(i).copied().unwrap_or(b' ');
+    let before = |n: usize| offset.checked_sub(n).map_or(b' ', at);
+
+    // As an object value, `1,000` fails at the digits after the comma, which
+    // read as a stray key. In an array the same bytes are two numbers, or a
+    // leading zero, so only a number straight after a `:` is diagnosed.
+    let digits_after = (1..=offset).take_while(|n| before(*n).is_ascii_digit()).count();
+    let comma = digits_after + 1;
+    let digits_before = (comma + 1..=offset).take_while(|n| before(*n).is_ascii_digit()).count();
+    let preceding = (comma + digits_before + 1..=offset).map(before).find(|b| !b.is_ascii_whitespace());
+    if before(comma) == b','
+        && digits_before > 0
+        && (digits_after > 0 || at(offset).is_ascii_digit())
+        && preceding == Some(b':')
+    {
+        return Some(format!("Invalid number at {}: numbers can't have thousands separators", position));
+    }
+
+    if !error.to_string().starts_with("invalid number") {
+        return None;
+    }
+    // `0100` fails at the `1`, right after a zero that starts the number.
+    if before(1) == b'0' && !before(2).is_ascii_digit() && before(2) != b'.' && at(offset).is_ascii_digit() {
+        return Some(format!("Invalid number at {}: numbers can't have leading zeros", position));
+    }
+    Some(format!("Invalid number at {}", position))
+}
+
+// The half-open range of an integer type as floats, to tell a float that
+// fits (`1e9` for a u64) from one that doesn't (2^64).
+fn integer_range(ty: &str) -> (f64, f64) {
+    let bits = ty[1..].parse::<i32>().unwrap_or(64);
+    if ty.starts_with('u') {
+        (0.0, 2f64.powi(bits))
+    } else {
+        (-(2f64.powi(bits - 1)), 2f64.powi(bits - 1))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use axum::{body::Body, http::header};
+    use serde::Deserialize;
+
+    #[allow(dead_code)]
+    #[derive(Debug, Deserialize)]
+    struct Transfer {
+        amount: u64,
+        recipients: Option<Vec<Recipient>>,
+        amounts: Option<Vec<u64>>,
+    }
+
+    #[allow(dead_code)]
+    #[derive(Debug, Deserialize)]
+    struct Recipient {
+        decimals: u8,
+    }
+
+    async fn error(body: &'static str) -> String {
+        let req = Request::post("/")
+            .header(header::CONTENT_TYPE, "application/json")
+            .body(Body::from(body))
+            .unwrap();
+        let (status, axum::Json(body)) = extract_json_with_error_status::<Transfer>(req).await.unwrap_err();
+        assert_eq!(status, StatusCode::BAD_REQUEST);
+        body["error"].as_str().unwrap().to_string()
+    }
+
+    #[tokio::test]
+    async fn numeric_errors_name_the_field() {
+        assert_eq!(
+            error(r#"{"amount": 18446744073709551616}"#).await,
+            "amount: number out of range for u64"
+        );
+        assert_eq!(error(r#"{"amount": -1}"#).await, "amount: number out of range for u64");
+        assert_eq!(error(r#"{"amount": 1.5}"#).await, "amount: expected a whole number for u64");
+        assert_eq!(
+            error(r#"{"amount": 1, "recipients": [{"decimals": 300}]}"#).await,
+            "recipients[0].decimals: number out of range for u8"
+        );
+        // Anything else keeps the generic message.
+        assert_eq!(error(r#"{"amount": true}"#).await, "Invalid or missing field in JSON request body");
+        assert_eq!(error(r#"{"amount": 1, "x" 2}"#).await, "Invalid or missing field in JSON request body");
+    }
+
+    #[tokio::test]
+    async fn ambiguous_amount_formats_are_rejected() {
+        let plain = "amount: write u64 values as plain integers, without an exponent or decimal point";
+        assert_eq!(error(r#"{"amount": 1e9}"#).await, plain);
+        assert_eq!(error(r#"{"amount": 1E3}"#).await, plain);
+        assert_eq!(error(r#"{"amount": 100.0}"#).await, plain);
+        assert_eq!(error(r#"{"amount": 1e20}"#).await, "amount: number out of range for u64");
+        assert_eq!(
+            error(r#"{"amount": 0100}"#).await,
+            "Invalid number at line 1 column 13: numbers can't have leading zeros"
+        );
+        assert_eq!(
+            error("{\n  \"recipients\": [{\"decimals\": 1}],\n  \"amount\": 007\n}").await,
+            "Invalid number at line 3 column 14: numbers can't have leading zeros"
+        );
+        assert_eq!(
+            error(r#"{"amount": 1,000}"#).await,
+            "Invalid number at line 1 column 14: numbers can't have thousands separators"
+        );
+        // In an array, a comma separates numbers, so the zero is the problem.
+        assert_eq!(
+            error(r#"{"amount": 1, "amounts": [10,0100]}"#).await,
+            "Invalid number at line 1 column 31: numbers can't have leading zeros"
+        );
+        assert_eq!(
+            error(r#"{"amount": 1, "recipients": [{"decimals": 1,000}]}"#).await,
+            "Invalid number at line 1 column 45: numbers can't have thousands separators"
+        );
+        // Malformed in some other way, so no particular cause is named.
+        assert_eq!(error(r#"{"amount": 1.}"#).await, "Invalid number at line 1 column 14");
+        assert_eq!(error(r#"{"amount": 1e}"#).await, "Invalid number at line 1 column 14");
+        assert_eq!(error(r#"{"amount": -}"#).await, "Invalid number at line 1 column 13");
+        assert_eq!(error(r#"{"amount": 0.05}"#).await, "amount: expected a whole number for u64");
+        assert_eq!(
+            error(r#"{"amount": "1000"}"#).await,
+            "amount: expected a JSON number for u64, not a string"
+        );
+        assert_eq!(
+            error(r#"{"amount": "1,000"}"#).await,
+            "amount: expected a JSON number for u64, not a string"
+        );
+        assert_eq!(
+            error(r#"{"amount": 1, "recipients": [{"decimals": 6e0}]}"#).await,
+            "recipients[0].decimals: write u8 values as plain integers, without an exponent or decimal point"
+        );
+    }
+
+    #[tokio::test]
+    async fn gzip_bodies_are_decompressed_and_limited() {
+        use axum::{routing::post, Router};
+        use flate2::{write::GzEncoder, Compression};
+        use std::io::Write;
+        use tower::ServiceExt;
+        use tower_http::decompression::RequestDecompressionLayer;
+
+        let app = Router::new()
+            .route(
+                "/",
+                post(|req: Request| async move {
+                    match extract_json_with_error_status::<Transfer>(req).await {
+                        Ok(Json(transfer)) => (StatusCode::OK, transfer.amount.to_string()),
+                        Err((status, body)) => (status, body["error"].to_string()),
+                    }
+                }),
+            )
+            .layer(RequestDecompressionLayer::new());
+        let post_gzip = |json: Vec<u8>| {
+            let app = app.clone();
+            async move {
+                let mut encoder = GzEncoder::new(Vec::new(), Compression::default());
+                encoder.write_all(&json).unwrap();
+                let req = Request::post("/")
+                    .header(header::CONTENT_TYPE, "application/json")
+                    .header(header::CONTENT_ENCODING, "gzip")
+                    .body(Body::from(encoder.finish().unwrap()))
+                    .unwrap();
+                let response = app.oneshot(req).await.unwrap();
+                let status = response.status();
+                let body = axum::body::to_bytes(response.into_body(), usize::MAX).await.unwrap();
+                (status, String::from_utf8(body.to_vec()).unwrap())
+            }
+        };
+
+        assert_eq!(
+            post_gzip(br#"{"amount": 42}"#.to_vec()).await,
+            (StatusCode::OK, "42".to_string())
+        );
+
+        // Compresses to a few kilobytes but inflates past the 2 MB limit.
+        let mut bomb = br#"{"amount": 1"#.to_vec();
+        bomb.resize(bomb.len() + 3 * 1024 * 1024, b' ');
+        bomb.push(b'}');
+        let (status, _) = post_gzip(bomb).await;
+        assert_eq!(status, StatusCode::PAYLOAD_TOO_LARGE);
     }
 }
@@ -1,26 +1,38 @@
+mod airdrop;
+mod cache;
+mod health;
 mod openapi;
+mod route_flags;
 mod routes;
+mod rpc_sender;
 mod json_extractor;
+mod middleware;
+mod state;
+mod telemetry;
+mod tls;
 
 use axum::{
+    error_handling::HandleErrorLayer,
+    extract::{Query, Request, State},
     routing::{get, post},
     Router,
     http::StatusCode,
-    response::IntoResponse,
-    Json,
 };
 use dotenv::dotenv;
 use openapi::ApiDoc;
-use routes::{get_balance, receive_message, generate_keypair, create_token, mint_token, sign_message, verify_message, send_sol, send_token, Message};
+use route_flags::{DisabledRoutes, RouterExt};
+use routes::{get_balance, get_epoch_info, receive_message, generate_keypair, create_token, mint_token, sign_message, verify_message, send_sol, send_token, find_pda, BuildOptions, batch_transfer_sol, token_account_status, sign_and_build, wrap_sol, unwrap_sol, recent_priority_fees, check_address, create_stake_account, SignMessageQuery, request_airdrop, create_token_metadata, CreateTokenQuery, SendTokenQuery, list_routes, server_pubkey, mint_token_checked, inspect_keypair, token_delegations, readiness, token_account_rent, get_latest_blockhash, rent_exempt_status, rpc_queue_metrics, create_ata_batch, runtime_config, verify_messages_batch, export_keypair, transaction_accounts, estimate_rent, get_leader_schedule, ed25519_instruction, get_multiple_accounts, keypair_with_airdrop, get_program_accounts, token_program, partial_sign_transaction, account_diff, create_immutable_token, check_derivation_path, convert_pubkey, cluster_performance, stake_minimum_delegation, transfer_with_seed, cluster_genesis, transaction_blockhash_status, close_mint, token_account_info, mint_info, cluster_supply, keypair_from_seed, stake_authorize, message_self_test, stake_withdraw, StreamQuery, stake_deactivate, solana_pay_transfer_url, solana_pay_parse};
+use state::AppState;
 use std::net::SocketAddr;
-use tracing_subscriber;
+use tower::{limit::GlobalConcurrencyLimitLayer, ServiceBuilder};
+use tower_http::decompression::RequestDecompressionLayer;
 use utoipa::OpenApi;
 use utoipa_swagger_ui::SwaggerUi;
 
 #[tokio::main]
 async fn main() {
     dotenv().ok();
-    tracing_subscriber::fmt::init();
+    telemetry::init();
 
     let port = std::env::var("PORT")
         .unwrap_or_else(|_| "3000".to_string())
@@ -28,46 +40,304 @@ async fn main() {
         .unwrap_or(3000);
 
     let addr = SocketAddr::from(([0, 0, 0, 0], port));
-    tracing::info!("🚀 Server running at http://{}", addr);
 
-    let app = Router::new()
-        .route("/submit", post(|req| async {
-            match crate::json_extractor::extract_json_with_error_status::<Message>(req).await {
-                Ok(Json(payload)) => receive_message(payload).await.into_response(),
-                Err(err) => err.0.into_response(),
+    // HTTPS only when both paths are set; a half-configured TLS setup is
+    // almost certainly a mistake, so refuse to start rather than serve plain HTTP.
+    let tls_config = match (std::env::var("TLS_CERT_PATH"), std::env::var("TLS_KEY_PATH")) {
+        (Ok(cert_path), Ok(key_path)) => match tls::TlsSettings::from_env()
+            .and_then(|settings| tls::load_config(&cert_path, &key_path, &settings))
+        {
+            Ok(config) => Some(config),
+            Err(e) => {
+                tracing::error!("Failed to load TLS configuration: {}", e);
+                std::process::exit(1);
             }
+        },
+        (Err(_), Err(_)) => None,
+        _ => {
+            tracing::error!("TLS_CERT_PATH and TLS_KEY_PATH must be set together");
+            std::process::exit(1);
+        }
+    };
+
+    let mut state = AppState::from_env();
+
+    let mut disabled_routes = match DisabledRoutes::from_env() {
+        Ok(disabled) => disabled,
+        Err(e) => {
+            tracing::error!("Invalid route configuration: {}", e);
+            std::process::exit(1);
+        }
+    };
+    state.disabled_routes = std::sync::Arc::new(disabled_routes.paths().to_vec());
+
+    let security_headers = match middleware::SecurityHeaders::from_env() {
+        Ok(headers) => headers.map(std::sync::Arc::new),
+        Err(e) => {
+            tracing::error!("Invalid security header configuration: {}", e);
+            std::process::exit(1);
+        }
+    };
+
+    let max_inflight_requests = match middleware::max_inflight_requests_from_env() {
+        Ok(max) => max,
+        Err(e) => {
+            tracing::error!("Invalid load shedding configuration: {}", e);
+            std::process::exit(1);
+        }
+    };
+
+    let mut app = Router::new()
+        .route_enabled(&mut disabled_routes, "/submit", post(|req| async {
+            let result = crate::json_extractor::extract_json_with_error_status(req).await;
+            receive_message(result).await
+        }))
+        .route_enabled(&mut disabled_routes, "/balance/{address}", get(get_balance))
+        .route_enabled(&mut disabled_routes, "/cluster/epoch", get(get_epoch_info))
+        .route_enabled(&mut disabled_routes, "/keypair", post(generate_keypair))
+        .route_enabled(&mut disabled_routes, "/token/create", post(|State(state): State<AppState>, Query(options): Query<BuildOptions>, Query(query): Query<CreateTokenQuery>, req: Request| async move {
+            let result = crate::json_extractor::extract_json_with_error_status(req).await;
+            create_token(state, result, options, query).await
+        }))
+        .route_enabled(&mut disabled_routes, "/token/mint", post(|State(state): State<AppState>, Query(options): Query<BuildOptions>, req: Request| async move {
+            let result = crate::json_extractor::extract_json_with_error_status(req).await;
+            mint_token(state, result, options).await
+        }))
+        .route_enabled(&mut disabled_routes, "/message/sign", post(|State(state): State<AppState>, Query(query): Query<SignMessageQuery>, req: Request| async move {
+            let result = crate::json_extractor::extract_json_with_error_status(req).await;
+            sign_message(state, result, query).await
+        }))
+        .route_enabled(&mut disabled_routes, "/message/verify", post(|State(state): State<AppState>, req: Request| async move {
+            let result = crate::json_extractor::extract_json_with_error_status(req).await;
+            verify_message(state, result).await
+        }))
+        .route_enabled(&mut disabled_routes, "/send/sol", post(|State(state): State<AppState>, Query(options): Query<BuildOptions>, req: Request| async move {
+            let result = crate::json_extractor::extract_json_with_error_status(req).await;
+            send_sol(state, result, options).await
+        }))
+        .route_enabled(&mut disabled_routes, "/send/token", post(|State(state): State<AppState>, Query(options): Query<BuildOptions>, Query(query): Query<SendTokenQuery>, req: Request| async move {
+            let result = crate::json_extractor::extract_json_with_error_status(req).await;
+            send_token(state, result, options, query).await
+        }))
+        .route_enabled(&mut disabled_routes, "/pda/find", post(|req| async {
+            let result = crate::json_extractor::extract_json_with_error_status(req).await;
+            find_pda(result).await
+        }))
+        .route_enabled(&mut disabled_routes, "/send/sol/batch", post(|State(state): State<AppState>, Query(options): Query<BuildOptions>, req: Request| async move {
+            let result = crate::json_extractor::extract_json_with_error_status(req).await;
+            batch_transfer_sol(state, result, options).await
+        }))
+        .route_enabled(&mut disabled_routes, "/token/account/status", post(|State(state): State<AppState>, req: Request| async move {
+            let result = crate::json_extractor::extract_json_with_error_status(req).await;
+            token_account_status(state, result).await
+        }))
+        .route_enabled(&mut disabled_routes, "/keypair/sign-and-build", post(|State(state): State<AppState>, Query(options): Query<BuildOptions>, req: Request| async move {
+            let result = crate::json_extractor::extract_json_with_error_status(req).await;
+            sign_and_build(state, result, options).await
+        }))
+        .route_enabled(&mut disabled_routes, "/token/wrap", post(|State(state): State<AppState>, Query(options): Query<BuildOptions>, req: Request| async move {
+            let result = crate::json_extractor::extract_json_with_error_status(req).await;
+            wrap_sol(state, result, options).await
+        }))
+        .route_enabled(&mut disabled_routes, "/token/unwrap", post(|State(state): State<AppState>, Query(options): Query<BuildOptions>, req: Request| async move {
+            let result = crate::json_extractor::extract_json_with_error_status(req).await;
+            unwrap_sol(state, result, options).await
+        }))
+        .route_enabled(&mut disabled_routes, "/fees/priority", get(recent_priority_fees))
+        .route_enabled(&mut disabled_routes, "/address/{address}", get(check_address))
+        .route_enabled(&mut disabled_routes, "/stake/create", post(|Query(options): Query<BuildOptions>, req: Request| async move {
+            let result = crate::json_extractor::extract_json_with_error_status(req).await;
+            create_stake_account(result, options).await
+        }))
+        .route_enabled(&mut disabled_routes, "/airdrop", post(|State(state): State<AppState>, req: Request| async move {
+            let result = crate::json_extractor::extract_json_with_error_status(req).await;
+            request_airdrop(state, result).await
+        }))
+        .route_enabled(&mut disabled_routes, "/token/create-metadata", post(|State(state): State<AppState>, Query(options): Query<BuildOptions>, req: Request| async move {
+            let result = crate::json_extractor::extract_json_with_error_status(req).await;
+            create_token_metadata(state, result, options).await
+        }))
+        .route_enabled(&mut disabled_routes, "/routes", get(list_routes))
+        .route_enabled(&mut disabled_routes, "/server-pubkey", get(server_pubkey))
+        .route_enabled(&mut disabled_routes, "/token/mint-checked", post(|State(state): State<AppState>, Query(options): Query<BuildOptions>, req: Request| async move {
+            let result = crate::json_extractor::extract_json_with_error_status(req).await;
+            mint_token_checked(state, result, options).await
+        }))
+        .route_enabled(&mut disabled_routes, "/keypair/inspect", post(|req| async {
+            let result = crate::json_extractor::extract_json_with_error_status(req).await;
+            inspect_keypair(result).await
+        }))
+        .route_enabled(&mut disabled_routes, "/token/delegations/{owner}", get(token_delegations))
+        .route_enabled(&mut disabled_routes, "/ready", get(readiness))
+        .route_enabled(&mut disabled_routes, "/token/account/rent", get(token_account_rent))
+        .route_enabled(&mut disabled_routes, "/blockhash", get(get_latest_blockhash))
+        .route_enabled(&mut disabled_routes, "/account/{address}/rent-exempt", get(rent_exempt_status))
+        .route_enabled(&mut disabled_routes, "/metrics/rpc", get(rpc_queue_metrics))
+        .route_enabled(&mut disabled_routes, "/token/create-ata/batch", post(|State(state): State<AppState>, Query(options): Query<BuildOptions>, req: Request| async move {
+            let result = crate::json_extractor::extract_json_with_error_status(req).await;
+            create_ata_batch(state, result, options).await
         }))
-        .route("/balance/{address}", get(get_balance))
-        .route("/keypair", post(generate_keypair))
-        .route("/token/create", post(|req| async {
+        .route_enabled(&mut disabled_routes, "/config", get(runtime_config))
+        .route_enabled(&mut disabled_routes, "/message/verify/batch", post(|State(state): State<AppState>, req: Request| async move {
             let result = crate::json_extractor::extract_json_with_error_status(req).await;
-            create_token(result).await
+            verify_messages_batch(state, result).await
         }))
-        .route("/token/mint", post(|req| async {
+        .route_enabled(&mut disabled_routes, "/keypair/export", post(|req| async {
             let result = crate::json_extractor::extract_json_with_error_status(req).await;
-            mint_token(result).await
+            export_keypair(result).await
         }))
-        .route("/message/sign", post(|req| async {
+        .route_enabled(&mut disabled_routes, "/transaction/accounts", post(|State(state): State<AppState>, req: Request| async move {
             let result = crate::json_extractor::extract_json_with_error_status(req).await;
-            sign_message(result).await
+            transaction_accounts(state, result).await
         }))
-        .route("/message/verify", post(|req| async {
+        .route_enabled(&mut disabled_routes, "/rent/estimate", post(|State(state): State<AppState>, req: Request| async move {
             let result = crate::json_extractor::extract_json_with_error_status(req).await;
-            verify_message(result).await
+            estimate_rent(state, result).await
         }))
-        .route("/send/sol", post(|req| async {
+        .route_enabled(&mut disabled_routes, "/cluster/leader-schedule", get(get_leader_schedule))
+        .route_enabled(&mut disabled_routes, "/ed25519/instruction", post(|State(state): State<AppState>, Query(options): Query<BuildOptions>, req: Request| async move {
             let result = crate::json_extractor::extract_json_with_error_status(req).await;
-            send_sol(result).await
+            ed25519_instruction(state, result, options).await
         }))
-        .route("/send/token", post(|req| async {
+        .route_enabled(&mut disabled_routes, "/accounts", post(|State(state): State<AppState>, req: Request| async move {
             let result = crate::json_extractor::extract_json_with_error_status(req).await;
-            send_token(result).await
+            get_multiple_accounts(state, result).await
+        }))
+        .route_enabled(&mut disabled_routes, "/keypair/airdrop", post(|State(state): State<AppState>, req: Request| async move {
+            let result = crate::json_extractor::extract_json_with_error_status(req).await;
+            keypair_with_airdrop(state, result).await
+        }))
+        .route_enabled(&mut disabled_routes, "/program/accounts", post(|State(state): State<AppState>, Query(query): Query<StreamQuery>, req: Request| async move {
+            let result = crate::json_extractor::extract_json_with_error_status(req).await;
+            get_program_accounts(state, result, query).await
+        }))
+        .route_enabled(&mut disabled_routes, "/token/program", post(|State(state): State<AppState>, req: Request| async move {
+            let result = crate::json_extractor::extract_json_with_error_status(req).await;
+            token_program(state, result).await
+        }))
+        .route_enabled(&mut disabled_routes, "/transaction/partial-sign", post(|req| async {
+            let result = crate::json_extractor::extract_json_with_error_status(req).await;
+            partial_sign_transaction(result).await
+        }))
+        .route_enabled(&mut disabled_routes, "/account/diff", post(|State(state): State<AppState>, req: Request| async move {
+            let result = crate::json_extractor::extract_json_with_error_status(req).await;
+            account_diff(state, result).await
+        }))
+        .route_enabled(&mut disabled_routes, "/token/create-immutable", post(|Query(options): Query<BuildOptions>, req: Request| async move {
+            let result = crate::json_extractor::extract_json_with_error_status(req).await;
+            create_immutable_token(result, options).await
+        }))
+        .route_enabled(&mut disabled_routes, "/keypair/derivation-path", post(|req| async {
+            let result = crate::json_extractor::extract_json_with_error_status(req).await;
+            check_derivation_path(result).await
+        }))
+        .route_enabled(&mut disabled_routes, "/pubkey/convert", post(|req| async {
+            let result = crate::json_extractor::extract_json_with_error_status(req).await;
+            convert_pubkey(result).await
+        }))
+        .route_enabled(&mut disabled_routes, "/cluster/performance", get(cluster_performance))
+        .route_enabled(&mut disabled_routes, "/stake/minimum-delegation", get(stake_minimum_delegation))
+        .route_enabled(&mut disabled_routes, "/send/sol/with-seed", post(|State(state): State<AppState>, Query(options): Query<BuildOptions>, req: Request| async move {
+            let result = crate::json_extractor::extract_json_with_error_status(req).await;
+            transfer_with_seed(state, result, options).await
+        }))
+        .route_enabled(&mut disabled_routes, "/cluster/genesis", get(cluster_genesis))
+        .route_enabled(&mut disabled_routes, "/transaction/blockhash", post(|State(state): State<AppState>, req: Request| async move {
+            let result = crate::json_extractor::extract_json_with_error_status(req).await;
+            transaction_blockhash_status(state, result).await
+        }))
+        .route_enabled(&mut disabled_routes, "/token/close-mint", post(|State(state): State<AppState>, Query(options): Query<BuildOptions>, req: Request| async move {
+            let result = crate::json_extractor::extract_json_with_error_status(req).await;
+            close_mint(state, result, options).await
+        }))
+        .route_enabled(&mut disabled_routes, "/token/account/info", post(|State(state): State<AppState>, req: Request| async move {
+            let result = crate::json_extractor::extract_json_with_error_status(req).await;
+            token_account_info(state, result).await
+        }))
+        .route_enabled(&mut disabled_routes, "/token/mint/info", post(|State(state): State<AppState>, req: Request| async move {
+            let result = crate::json_extractor::extract_json_with_error_status(req).await;
+            mint_info(state, result).await
+        }))
+        .route_enabled(&mut disabled_routes, "/cluster/supply", get(cluster_supply))
+        .route_enabled(&mut disabled_routes, "/keypair/from-seed", post(|req| async {
+            let result = crate::json_extractor::extract_json_with_error_status(req).await;
+            keypair_from_seed(result).await
+        }))
+        .route_enabled(&mut disabled_routes, "/stake/authorize", post(|State(state): State<AppState>, Query(options): Query<BuildOptions>, req: Request| async move {
+            let result = crate::json_extractor::extract_json_with_error_status(req).await;
+            stake_authorize(state, result, options).await
+        }))
+        .route_enabled(&mut disabled_routes, "/message/self-test", post(|State(state): State<AppState>, req: Request| async move {
+            let result = crate::json_extractor::extract_json_with_error_status(req).await;
+            message_self_test(state, result).await
+        }))
+        .route_enabled(&mut disabled_routes, "/stake/withdraw", post(|State(state): State<AppState>, Query(options): Query<BuildOptions>, req: Request| async move {
+            let result = crate::json_extractor::extract_json_with_error_status(req).await;
+            stake_withdraw(state, result, options).await
+        }))
+        .route_enabled(&mut disabled_routes, "/stake/deactivate", post(|State(state): State<AppState>, Query(options): Query<BuildOptions>, req: Request| async move {
+            let result = crate::json_extractor::extract_json_with_error_status(req).await;
+            stake_deactivate(state, result, options).await
+        }))
+        .route_enabled(&mut disabled_routes, "/solana-pay/transfer-url", post(|req| async {
+            let result = crate::json_extractor::extract_json_with_error_status(req).await;
+            solana_pay_transfer_url(result).await
+        }))
+        .route_enabled(&mut disabled_routes, "/solana-pay/parse", post(|req| async {
+            let result = crate::json_extractor::extract_json_with_error_status(req).await;
+            solana_pay_parse(result).await
         }))
         .merge(SwaggerUi::new("/").url("/api-docs/openapi.json", ApiDoc::openapi()))
         .fallback_service(get(|| async {
             (StatusCode::NOT_FOUND, "Not Found")
-        }));
+        }))
+        // Inflates `Content-Encoding: gzip` bodies before the handlers see
+        // them, so axum's 2 MB body limit counts decompressed bytes.
+        .layer(RequestDecompressionLayer::new())
+        .layer(axum::middleware::from_fn_with_state(state.clone(), middleware::request_deadline));
+    // One limit shared by every route. Inside `api_version`, so the 503s use
+    // the envelope the client asked for.
+    if let Some(max) = max_inflight_requests {
+        app = app.layer(
+            ServiceBuilder::new()
+                .layer(HandleErrorLayer::new(middleware::overloaded))
+                .load_shed()
+                .layer(GlobalConcurrencyLimitLayer::new(max)),
+        );
+    }
+    let mut app = app
+        .layer(axum::middleware::from_fn(middleware::api_version))
+        .layer(axum::middleware::from_fn_with_state(state.clone(), middleware::rpc_queue_depth))
+        .layer(axum::middleware::from_fn(middleware::pretty_json))
+        .layer(axum::middleware::from_fn_with_state(state.clone(), middleware::sign_response));
+    // Covers every response, including the fallback and deadline 504s.
+    if let Some(headers) = security_headers {
+        app = app.layer(axum::middleware::from_fn_with_state(headers, middleware::security_headers));
+    }
+    let app = app
+        // Outside `request_deadline`, so deadline 504s are logged too.
+        .layer(axum::middleware::from_fn_with_state(state.clone(), middleware::request_log))
+        // Outermost, so the request span covers logging as well.
+        .layer(axum::middleware::from_fn(telemetry::request_span))
+        .with_state(state);
+
+    // A typo here would silently leave the route exposed, so refuse to start.
+    let unmatched = disabled_routes.unmatched();
+    if !unmatched.is_empty() {
+        tracing::error!("DISABLED_ROUTES entries match no route: {}", unmatched.join(", "));
+        std::process::exit(1);
+    }
 
     let listener = tokio::net::TcpListener::bind(addr).await.unwrap();
-    axum::serve(listener, app).await.unwrap();
+    match tls_config {
+        Some(config) => {
+            tracing::info!("🚀 Server running at https://{}", addr);
+            let listener = tls::TlsListener::new(listener, config).unwrap();
+            axum::serve(listener, app).await.unwrap();
+        }
+        None => {
+            tracing::info!("🚀 Server running at http://{}", addr);
+            axum::serve(listener, app).await.unwrap();
+        }
+    }
 }
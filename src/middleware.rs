@@ -0,0 +1,565 @@
+use crate::{
+    routes::{ApiResponse, EnvelopeVersion, ENVELOPE_VERSION},
+    state::AppState,
+};
+use axum::{
+    body::Body,
+    extract::{Request, State},
+    http::{header, HeaderValue, StatusCode},
+    middleware::Next,
+    response::IntoResponse,
+};
+use solana_sdk::signature::Signer;
+use std::{
+    sync::Arc,
+    time::{Duration, Instant},
+};
+use tower::{load_shed::error::Overloaded, BoxError};
+
+pub const REQUEST_TIMEOUT_HEADER: &str = "x-request-timeout-ms";
+pub const SIGNATURE_HEADER: &str = "x-signature";
+pub const RPC_QUEUE_DEPTH_HEADER: &str = "x-rpc-queue-depth";
+pub const API_VERSION_HEADER: &str = "x-api-version";
+
+// Picks the response envelope from `X-Api-Version`, falling back to `?v=`,
+// and defaulting to v1. Every `ApiResponse` built while the request runs
+// reads the choice, and the response echoes it back in `X-Api-Version`.
+pub async fn api_version(req: Request, next: Next) -> axum::response::Response {
+    let requested = match req.headers().get(API_VERSION_HEADER) {
+        Some(value) => Some(value.to_str().unwrap_or_default().to_string()),
+        None => req.uri().query().and_then(|q| {
+            q.split('&')
+                .find_map(|pair| pair.strip_prefix("v="))
+                .map(str::to_string)
+        }),
+    };
+    let version = match requested.as_deref().map(EnvelopeVersion::parse) {
+        None => EnvelopeVersion::V1,
+        Some(Some(version)) => version,
+        Some(None) => {
+            return ApiResponse::<()>::Error {
+                success: false,
+                error: format!("Unsupported API version {:?}; expected 1 or 2", requested.unwrap_or_default()),
+            }
+            .into_response();
+        }
+    };
+
+    let mut response = ENVELOPE_VERSION.scope(version, next.run(req)).await;
+    response
+        .headers_mut()
+        .insert(API_VERSION_HEADER, HeaderValue::from(version.number()));
+    response
+}
+
+// Gives up on a request once the client-supplied deadline passes. Dropping
+// the handler future also drops any RPC call it is awaiting, so abandoned
+// requests stop consuming upstream capacity.
+pub async fn request_deadline(
+    State(state): State<AppState>,
+    req: Request,
+    next: Next,
+) -> axum::response::Response {
+    let requested = req
+        .headers()
+        .get(REQUEST_TIMEOUT_HEADER)
+        .and_then(|v| v.to_str().ok())
+        .and_then(|v| v.parse::<u64>().ok());
+
+    let Some(requested) = requested else {
+        return next.run(req).await;
+    };
+    let timeout = Duration::from_millis(requested).min(state.max_request_timeout);
+
+    match tokio::time::timeout(timeout, next.run(req)).await {
+        Ok(response) => response,
+        Err(_) => (
+            StatusCode::GATEWAY_TIMEOUT,
+            ApiResponse::<()>::Error {
+                success: false,
+                error: format!("Request exceeded its {} ms deadline", timeout.as_millis()),
+            },
+        )
+            .into_response(),
+    }
+}
+
+// Reports how many RPC calls are waiting for a concurrency slot as the
+// response goes out, when RPC_QUEUE_DEPTH_HEADER is enabled.
+pub async fn rpc_queue_depth(
+    State(state): State<AppState>,
+    req: Request,
+    next: Next,
+) -> axum::response::Response {
+    let mut response = next.run(req).await;
+    if state.rpc_queue_header {
+        response
+            .headers_mut()
+            .insert(RPC_QUEUE_DEPTH_HEADER, HeaderValue::from(state.rpc_queue.depth()));
+    }
+    response
+}
+
+// MAX_INFLIGHT_REQUESTS caps how many requests the server works on at once.
+// Unset or 0 leaves it unbounded; anything unparseable is refused at startup.
+pub fn max_inflight_requests_from_env() -> Result<Option<usize>, String> {
+    match std::env::var("MAX_INFLIGHT_REQUESTS") {
+        Err(_) => Ok(None),
+        Ok(value) => value
+            .trim()
+            .parse::<usize>()
+            .map(|max| Some(max).filter(|max| *max > 0))
+            .map_err(|_| format!("MAX_INFLIGHT_REQUESTS must be a whole number, got {:?}", value)),
+    }
+}
+
+// Answers requests the load-shedding layer turned away because
+// MAX_INFLIGHT_REQUESTS were already in flight. They are rejected at once
+// rather than queued, so clients can back off or try another instance.
+pub async fn overloaded(error: BoxError) -> axum::response::Response {
+    let (status, error) = if error.is::<Overloaded>() {
+        (StatusCode::SERVICE_UNAVAILABLE, "Server is overloaded, retry shortly".to_string())
+    } else {
+        (StatusCode::INTERNAL_SERVER_ERROR, format!("Unhandled error: {}", error))
+    };
+    (
+        status,
+        [(header::RETRY_AFTER, HeaderValue::from_static("1"))],
+        ApiResponse::<()>::Error { success: false, error },
+    )
+        .into_response()
+}
+
+// Signs the exact response bytes with the server key so clients can check
+// them against `/server-pubkey`. The signature is base58, like every other
+// signature the server returns. Newline-delimited JSON streams are left
+// unsigned, since signing would mean buffering the whole stream first.
+pub async fn sign_response(
+    State(state): State<AppState>,
+    req: Request,
+    next: Next,
+) -> axum::response::Response {
+    let response = next.run(req).await;
+    let Some(keypair) = &state.signing_key else {
+        return response;
+    };
+    let is_stream = response
+        .headers()
+        .get(header::CONTENT_TYPE)
+        .is_some_and(|v| v.as_bytes().starts_with(b"application/x-ndjson"));
+    if is_stream {
+        return response;
+    }
+
+    let (mut parts, body) = response.into_parts();
+    let bytes = match axum::body::to_bytes(body, usize::MAX).await {
+        Ok(bytes) => bytes,
+        Err(e) => {
+            tracing::error!("Failed to buffer response for signing: {}", e);
+            return StatusCode::INTERNAL_SERVER_ERROR.into_response();
+        }
+    };
+
+    let signature = keypair.sign_message(&bytes);
+    if let Ok(value) = HeaderValue::from_str(&signature.to_string()) {
+        parts.headers.insert(SIGNATURE_HEADER, value);
+    }
+    axum::response::Response::from_parts(parts, Body::from(bytes))
+}
+
+// With `?pretty=true`, re-indents JSON responses for reading in a terminal.
+// Runs inside `sign_response`, so the signature covers the bytes sent.
+pub async fn pretty_json(req: Request, next: Next) -> axum::response::Response {
+    let pretty = req
+        .uri()
+        .query()
+        .is_some_and(|q| q.split('&').any(|pair| pair == "pretty=true"));
+    let response = next.run(req).await;
+    let is_json = response
+        .headers()
+        .get(header::CONTENT_TYPE)
+        .and_then(|v| v.to_str().ok())
+        .is_some_and(|v| v.starts_with("application/json"));
+    if !pretty || !is_json {
+        return response;
+    }
+
+    let (mut parts, body) = response.into_parts();
+    let bytes = match axum::body::to_bytes(body, usize::MAX).await {
+        Ok(bytes) => bytes,
+        Err(e) => {
+            tracing::error!("Failed to buffer response for pretty-printing: {}", e);
+            return StatusCode::INTERNAL_SERVER_ERROR.into_response();
+        }
+    };
+    parts.headers.remove(header::CONTENT_LENGTH);
+    axum::response::Response::from_parts(parts, Body::from(indent_json(&bytes)))
+}
+
+// Indents compact JSON the way `serde_json::to_string_pretty` does. Going
+// through `serde_json::Value` instead would sort every object's keys.
+fn indent_json(compact: &[u8]) -> Vec<u8> {
+    let mut out = Vec::with_capacity(compact.len() * 2);
+    let mut depth = 0usize;
+    let mut in_string = false;
+    let mut escaped = false;
+    let newline = |out: &mut Vec<u8>, depth: usize| {
+        out.push(b'\n');
+        out.extend(std::iter::repeat_n(b' ', depth * 2));
+    };
+
+    for (i, &byte) in compact.iter().enumerate() {
+        if in_string {
+            out.push(byte);
+            match byte {
+                _ if escaped => escaped = false,
+                b'\\' => escaped = true,
+                b'"' => in_string = false,
+                _ => {}
+            }
+            continue;
+        }
+        match byte {
+            b'"' => {
+                in_string = true;
+                out.push(byte);
+            }
+            b'{' | b'[' => {
+                out.push(byte);
+                // Empty containers stay on one line.
+                if !matches!(compact.get(i + 1), Some(b'}') | Some(b']')) {
+                    depth += 1;
+                    newline(&mut out, depth);
+                }
+            }
+            b'}' | b']' => {
+                if !matches!(i.checked_sub(1).map(|j| compact[j]), Some(b'{') | Some(b'[')) {
+                    depth = depth.saturating_sub(1);
+                    newline(&mut out, depth);
+                }
+                out.push(byte);
+            }
+            b',' => {
+                out.push(byte);
+                newline(&mut out, depth);
+            }
+            b':' => out.extend_from_slice(b": "),
+            _ => out.push(byte),
+        }
+    }
+    out
+}
+
+// Lets the bundled Swagger UI load: its scripts and stylesheets are served
+// from this origin, but it sets inline styles and uses data: URIs for icons.
+pub const DEFAULT_CONTENT_SECURITY_POLICY: &str = "default-src 'self'; script-src 'self'; \
+    style-src 'self' 'unsafe-inline'; img-src 'self' data:; frame-ancestors 'none'; \
+    base-uri 'none'; form-action 'self'";
+
+// Standard hardening headers for public deployments. SECURITY_HEADERS=false
+// turns them all off; CONTENT_SECURITY_POLICY replaces the default policy,
+// and an empty value leaves CSP out.
+#[derive(Debug, Clone)]
+pub struct SecurityHeaders {
+    content_security_policy: Option<HeaderValue>,
+}
+
+impl SecurityHeaders {
+    pub fn new(content_security_policy: Option<HeaderValue>) -> Self {
+        Self { content_security_policy }
+    }
+
+    pub fn from_env() -> Result<Option<Self>, String> {
+        let enabled = std::env::var("SECURITY_HEADERS")
+            .map(|v| !v.eq_ignore_ascii_case("false"))
+            .unwrap_or(true);
+        if !enabled {
+            return Ok(None);
+        }
+
+        let policy = std::env::var("CONTENT_SECURITY_POLICY")
+            .unwrap_or_else(|_| DEFAULT_CONTENT_SECURITY_POLICY.to_string());
+        let content_security_policy = match policy.trim() {
+            "" => None,
+            policy => Some(
+                HeaderValue::from_str(policy)
+                    .map_err(|_| "CONTENT_SECURITY_POLICY is not a valid header value".to_string())?,
+            ),
+        };
+        Ok(Some(Self::new(content_security_policy)))
+    }
+}
+
+// Headers a handler already set are left alone.
+pub async fn security_headers(
+    State(headers): State<Arc<SecurityHeaders>>,
+    req: Request,
+    next: Next,
+) -> axum::response::Response {
+    let mut response = next.run(req).await;
+    let target = response.headers_mut();
+    let mut set = |name, value| {
+        target.entry(name).or_insert(value);
+    };
+    set(header::X_CONTENT_TYPE_OPTIONS, HeaderValue::from_static("nosniff"));
+    set(header::X_FRAME_OPTIONS, HeaderValue::from_static("DENY"));
+    set(header::REFERRER_POLICY, HeaderValue::from_static("no-referrer"));
+    if let Some(policy) = &headers.content_security_policy {
+        set(header::CONTENT_SECURITY_POLICY, policy.clone());
+    }
+    response
+}
+
+// Access log with sampling. Only the method, path, status, and latency are
+// recorded; bodies (which may carry secrets) and query strings never are.
+pub async fn request_log(
+    State(state): State<AppState>,
+    req: Request,
+    next: Next,
+) -> axum::response::Response {
+    let method = req.method().clone();
+    let path = req.uri().path().to_string();
+    let started = Instant::now();
+
+    let response = next.run(req).await;
+
+    let status = response.status();
+    if should_log(status, state.log_sample_rate, rand::random::<f64>()) {
+        let latency_ms = started.elapsed().as_millis();
+        if status.is_client_error() || status.is_server_error() {
+            tracing::warn!(%method, %path, status = status.as_u16(), latency_ms, "request failed");
+        } else {
+            tracing::info!(%method, %path, status = status.as_u16(), latency_ms, "request");
+        }
+    }
+    response
+}
+
+// `roll` is uniform in [0, 1), so a sample rate of 1.0 logs everything and
+// 0.0 logs only errors.
+fn should_log(status: StatusCode, sample_rate: f64, roll: f64) -> bool {
+    status.is_client_error() || status.is_server_error() || roll < sample_rate
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use axum::{routing::get, Router};
+    use solana_sdk::signature::{Keypair, Signature};
+    use std::str::FromStr;
+    use tower::ServiceExt;
+
+    #[test]
+    fn errors_are_logged_regardless_of_sampling() {
+        for status in [StatusCode::BAD_REQUEST, StatusCode::NOT_FOUND, StatusCode::BAD_GATEWAY] {
+            assert!(should_log(status, 0.0, 0.99));
+        }
+    }
+
+    #[test]
+    fn successes_follow_the_sample_rate() {
+        assert!(!should_log(StatusCode::OK, 0.0, 0.0));
+        assert!(should_log(StatusCode::OK, 0.25, 0.1));
+        assert!(!should_log(StatusCode::OK, 0.25, 0.5));
+        assert!(should_log(StatusCode::OK, 1.0, 0.999));
+    }
+
+    #[tokio::test]
+    async fn signature_covers_the_response_body() {
+        let mut state = AppState::from_env();
+        let keypair = Arc::new(Keypair::new());
+        state.signing_key = Some(keypair.clone());
+
+        let app = Router::new()
+            .route("/", get(|| async { "signed body" }))
+            .layer(axum::middleware::from_fn_with_state(state, sign_response));
+        let response = app
+            .oneshot(Request::builder().uri("/").body(Body::empty()).unwrap())
+            .await
+            .unwrap();
+
+        let signature = response.headers()[SIGNATURE_HEADER].to_str().unwrap().to_string();
+        let body = axum::body::to_bytes(response.into_body(), usize::MAX).await.unwrap();
+        assert_eq!(&body[..], b"signed body");
+        assert!(Signature::from_str(&signature)
+            .unwrap()
+            .verify(keypair.pubkey().as_ref(), &body));
+    }
+
+    #[tokio::test]
+    async fn streams_are_not_signed() {
+        let mut state = AppState::from_env();
+        state.signing_key = Some(Arc::new(Keypair::new()));
+
+        let app = Router::new()
+            .route(
+                "/",
+                get(|| async { ([(header::CONTENT_TYPE, "application/x-ndjson")], "{}\n") }),
+            )
+            .layer(axum::middleware::from_fn_with_state(state, sign_response));
+        let response = app
+            .oneshot(Request::builder().uri("/").body(Body::empty()).unwrap())
+            .await
+            .unwrap();
+        assert!(response.headers().get(SIGNATURE_HEADER).is_none());
+    }
+
+    #[test]
+    fn indent_json_matches_serde_pretty_output() {
+        let value = serde_json::json!({
+            "data": { "empty": [], "list": [1, 2], "note": "a \"quoted\" {x: [1]}" },
+            "success": true
+        });
+        let compact = serde_json::to_vec(&value).unwrap();
+        let pretty = serde_json::to_string_pretty(&value).unwrap();
+        assert_eq!(String::from_utf8(indent_json(&compact)).unwrap(), pretty);
+    }
+
+    #[tokio::test]
+    async fn pretty_query_indents_json_responses() {
+        let app = Router::new()
+            .route("/", get(|| async { axum::Json(serde_json::json!({ "success": true })) }))
+            .layer(axum::middleware::from_fn(pretty_json));
+        let body = |uri: &'static str| {
+            let app = app.clone();
+            async move {
+                let response = app
+                    .oneshot(Request::builder().uri(uri).body(Body::empty()).unwrap())
+                    .await
+                    .unwrap();
+                axum::body::to_bytes(response.into_body(), usize::MAX).await.unwrap()
+            }
+        };
+
+        assert_eq!(&body("/?pretty=true").await[..], b"{\n  \"success\": true\n}");
+        assert_eq!(&body("/").await[..], b"{\"success\":true}");
+    }
+
+    #[tokio::test]
+    async fn security_headers_are_added_to_responses() {
+        let headers = Arc::new(SecurityHeaders::new(Some(HeaderValue::from_static(
+            DEFAULT_CONTENT_SECURITY_POLICY,
+        ))));
+        let app = Router::new()
+            .route("/", get(|| async { "ok" }))
+            .route(
+                "/framed",
+                get(|| async { ([(header::X_FRAME_OPTIONS, "SAMEORIGIN")], "ok") }),
+            )
+            .layer(axum::middleware::from_fn_with_state(headers, security_headers));
+        let get_headers = |uri: &'static str| {
+            let app = app.clone();
+            async move {
+                let response = app
+                    .oneshot(Request::builder().uri(uri).body(Body::empty()).unwrap())
+                    .await
+                    .unwrap();
+                response.headers().clone()
+            }
+        };
+
+        let headers = get_headers("/").await;
+        assert_eq!(headers[header::X_CONTENT_TYPE_OPTIONS], "nosniff");
+        assert_eq!(headers[header::X_FRAME_OPTIONS], "DENY");
+        assert_eq!(headers[header::REFERRER_POLICY], "no-referrer");
+        assert_eq!(headers[header::CONTENT_SECURITY_POLICY], DEFAULT_CONTENT_SECURITY_POLICY);
+
+        assert_eq!(get_headers("/framed").await[header::X_FRAME_OPTIONS], "SAMEORIGIN");
+    }
+
+    #[tokio::test]
+    async fn api_version_selects_the_envelope() {
+        let app = Router::new()
+            .route(
+                "/ok",
+                get(|| async { ApiResponse::Success { success: true, data: 7 } }),
+            )
+            .route(
+                "/fail",
+                get(|| async {
+                    ApiResponse::<()>::ErrorWithCode {
+                        success: false,
+                        error: "bad".to_string(),
+                        code: "BAD".to_string(),
+                    }
+                }),
+            )
+            .layer(axum::middleware::from_fn(api_version));
+        let call = |uri: &'static str, version: Option<&'static str>| {
+            let app = app.clone();
+            async move {
+                let mut request = Request::builder().uri(uri);
+                if let Some(version) = version {
+                    request = request.header(API_VERSION_HEADER, version);
+                }
+                let response = app.oneshot(request.body(Body::empty()).unwrap()).await.unwrap();
+                let status = response.status();
+                let echoed = response.headers().get(API_VERSION_HEADER).cloned();
+                let body = axum::body::to_bytes(response.into_body(), usize::MAX).await.unwrap();
+                let body: serde_json::Value = serde_json::from_slice(&body).unwrap();
+                (status, echoed, body)
+            }
+        };
+
+        let (_, echoed, body) = call("/ok", None).await;
+        assert_eq!(body, serde_json::json!({ "success": true, "data": 7 }));
+        assert_eq!(echoed.unwrap(), "1");
+
+        let (_, echoed, body) = call("/ok", Some("2")).await;
+        assert_eq!(body, serde_json::json!({ "status": "success", "data": 7 }));
+        assert_eq!(echoed.unwrap(), "2");
+
+        let (status, _, body) = call("/fail?v=2", None).await;
+        assert_eq!(status, StatusCode::BAD_REQUEST);
+        assert_eq!(
+            body,
+            serde_json::json!({ "status": "error", "error": { "message": "bad", "code": "BAD" } })
+        );
+
+        let (status, echoed, body) = call("/ok", Some("3")).await;
+        assert_eq!(status, StatusCode::BAD_REQUEST);
+        assert!(echoed.is_none());
+        assert_eq!(body["success"], false);
+    }
+
+    #[tokio::test]
+    async fn requests_beyond_the_inflight_limit_are_shed() {
+        use axum::error_handling::HandleErrorLayer;
+        use tower::{limit::GlobalConcurrencyLimitLayer, ServiceBuilder};
+
+        let release = Arc::new(tokio::sync::Notify::new());
+        let app = Router::new()
+            .route(
+                "/",
+                get({
+                    let release = release.clone();
+                    || async move { release.notified().await }
+                }),
+            )
+            .layer(
+                ServiceBuilder::new()
+                    .layer(HandleErrorLayer::new(overloaded))
+                    .load_shed()
+                    .layer(GlobalConcurrencyLimitLayer::new(1)),
+            );
+        let request = || Request::builder().uri("/").body(Body::empty()).unwrap();
+
+        let held = tokio::spawn(app.clone().oneshot(request()));
+        tokio::task::yield_now().await;
+        assert!(!held.is_finished());
+        let shed = app.clone().oneshot(request()).await.unwrap();
+        assert_eq!(shed.status(), StatusCode::SERVICE_UNAVAILABLE);
+        assert_eq!(shed.headers()[header::RETRY_AFTER], "1");
+        let body = axum::body::to_bytes(shed.into_body(), usize::MAX).await.unwrap();
+        let body: serde_json::Value = serde_json::from_slice(&body).unwrap();
+        assert_eq!(body["success"], false);
+
+        release.notify_one();
+        assert_eq!(held.await.unwrap().unwrap().status(), StatusCode::OK);
+        // The slot is free again once the first request finishes.
+        let again = tokio::spawn(app.oneshot(request()));
+        tokio::task::yield_now().await;
+        release.notify_one();
+        assert_eq!(again.await.unwrap().unwrap().status(), StatusCode::OK);
+    }
+}
@@ -1,4 +1,7 @@
-use crate::routes::{BalanceResponse, Message, Response, TokenCreateRequest, TokenMintRequest, MessageSignRequest, MessageVerifyRequest, SendSolRequest, SendTokenRequest};
+use crate::health::DependencyStatus;
+use crate::rpc_sender::RpcQueueMetrics;
+use crate::state::Limits;
+use crate::routes::{ErrorResponse, KeypairResponse, SignMessageResponse, AccountMetaResponse, TokenTransferAccount, InstructionAccounts, DryRunInfo, InstructionResponse, BatchTransferSolResponse, WrapSolResponse, UnwrapSolResponse, VerifyMessageResponse, BalanceResponse, EpochInfoResponse, InflationRateResponse, Message, Response, TokenCreateRequest, TokenMintRequest, MessageSignRequest, MessageVerifyRequest, SendSolRequest, SendTokenRequest, FindPdaRequest, FindPdaResponse, SeedEncoding, BatchTransferSolRequest, BatchRecipient, TokenAccountStatusRequest, TokenAccountStatusResponse, SignAndBuildRequest, SignAndBuildResponse, BuildInstructionRequest, WrapSolRequest, UnwrapSolRequest, PriorityFeeResponse, AddressCheckResponse, StakeCreateRequest, StakeCreateResponse, AirdropRequest, AirdropResponse, TokenMetadataRequest, TokenMetadataResponse, RouteInfo, ServerPubkeyResponse, TokenMintCheckedRequest, KeypairInspectRequest, KeypairInspectResponse, TokenDelegation, TokenDelegationsResponse, ReadyResponse, TokenAccountRentResponse, BlockhashResponse, RentExemptResponse, CreateAtaBatchRequest, AssociatedTokenAccountEntry, CreateAtaBatchResponse, FeatureFlags, ConfigResponse, VerifyMessagesBatchRequest, VerifyMessageResult, VerifyMessagesBatchResponse, KeypairExportRequest, KeypairExportResponse, AccountMetaInput, InstructionInput, TransactionAccountsRequest, TransactionAccountSummary, TransactionAccountsResponse, SolanaPayResponse, RentEstimateRequest, RentEstimateEntry, RentEstimateResponse, LeaderScheduleResponse, Ed25519InstructionRequest, MultipleAccountsRequest, AccountSummary, MultipleAccountsEntry, MultipleAccountsResponse, KeypairAirdropRequest, KeypairAirdropResponse, MemcmpBytesEncoding, MemcmpFilter, ProgramAccountsFilter, DataSlice, ProgramAccountsRequest, ProgramAccountEntry, ProgramAccountsResponse, MessageSignature, MultiSignMessageResponse, TokenProgramRequest, TokenProgramResponse, PartialSignRequest, PartialSignResponse, AccountDiffRequest, FieldChange, ByteRangeChange, AccountDiffResponse, TokenCreateImmutableResponse, DerivationPathRequest, DerivationPathResponse, PubkeyEncoding, ConvertPubkeyRequest, ConvertPubkeyResponse, ClusterPerformanceResponse, StakeMinimumResponse, TransferWithSeedRequest, GenesisResponse, BlockhashStatusRequest, BlockhashStatusResponse, TokenCloseMintRequest, TokenAccountInfoRequest, TokenAccountInfoResponse, MintInfoRequest, MintInfoResponse, SupplyResponse, KeypairFromSeedRequest, StakeAuthorizeKind, StakeAuthorizeRequest, MessageSelfTestRequest, MessageSelfTestResponse, StakeWithdrawRequest, StakeDeactivateRequest, SolanaPayUrlRequest, SolanaPayUrlResponse, SolanaPayParseRequest, SolanaPayRequestKind, SolanaPayParseResponse};
 use utoipa::OpenApi;
 
 #[derive(OpenApi)]
@@ -6,15 +9,125 @@ use utoipa::OpenApi;
     paths(
         crate::routes::receive_message,
         crate::routes::get_balance,
+        crate::routes::get_epoch_info,
         crate::routes::generate_keypair,
         crate::routes::create_token,
         crate::routes::mint_token,
         crate::routes::sign_message,
         crate::routes::verify_message,
         crate::routes::send_sol,
-        crate::routes::send_token
+        crate::routes::send_token,
+        crate::routes::find_pda,
+        crate::routes::batch_transfer_sol,
+        crate::routes::token_account_status,
+        crate::routes::sign_and_build,
+        crate::routes::wrap_sol,
+        crate::routes::unwrap_sol,
+        crate::routes::recent_priority_fees,
+        crate::routes::check_address,
+        crate::routes::create_stake_account,
+        crate::routes::request_airdrop,
+        crate::routes::create_token_metadata,
+        crate::routes::list_routes,
+        crate::routes::server_pubkey,
+        crate::routes::mint_token_checked,
+        crate::routes::inspect_keypair,
+        crate::routes::token_delegations,
+        crate::routes::readiness,
+        crate::routes::token_account_rent,
+        crate::routes::get_latest_blockhash,
+        crate::routes::rent_exempt_status,
+        crate::routes::rpc_queue_metrics,
+        crate::routes::create_ata_batch,
+        crate::routes::runtime_config,
+        crate::routes::verify_messages_batch,
+        crate::routes::export_keypair,
+        crate::routes::transaction_accounts,
+        crate::routes::estimate_rent,
+        crate::routes::get_leader_schedule,
+        crate::routes::ed25519_instruction,
+        crate::routes::get_multiple_accounts,
+        crate::routes::keypair_with_airdrop,
+        crate::routes::get_program_accounts,
+        crate::routes::token_program,
+        crate::routes::partial_sign_transaction,
+        crate::routes::account_diff,
+        crate::routes::create_immutable_token,
+        crate::routes::check_derivation_path,
+        crate::routes::convert_pubkey,
+        crate::routes::cluster_performance,
+        crate::routes::stake_minimum_delegation,
+        crate::routes::transfer_with_seed,
+        crate::routes::cluster_genesis,
+        crate::routes::transaction_blockhash_status,
+        crate::routes::close_mint,
+        crate::routes::token_account_info,
+        crate::routes::mint_info,
+        crate::routes::cluster_supply,
+        crate::routes::keypair_from_seed,
+        crate::routes::stake_authorize,
+        crate::routes::message_self_test,
+        crate::routes::stake_withdraw,
+        crate::routes::stake_deactivate,
+        crate::routes::solana_pay_transfer_url,
+        crate::routes::solana_pay_parse
     ),
-    components(schemas(Message, Response, BalanceResponse, TokenCreateRequest, TokenMintRequest, MessageSignRequest, MessageVerifyRequest, SendSolRequest, SendTokenRequest)),
+    components(schemas(ErrorResponse, KeypairResponse, SignMessageResponse, AccountMetaResponse, TokenTransferAccount, InstructionAccounts, DryRunInfo, InstructionResponse, BatchTransferSolResponse, WrapSolResponse, UnwrapSolResponse, VerifyMessageResponse, Message, Response, BalanceResponse, EpochInfoResponse, InflationRateResponse, TokenCreateRequest, TokenMintRequest, MessageSignRequest, MessageVerifyRequest, SendSolRequest, SendTokenRequest, FindPdaRequest, FindPdaResponse, SeedEncoding, BatchTransferSolRequest, BatchRecipient, TokenAccountStatusRequest, TokenAccountStatusResponse, SignAndBuildRequest, SignAndBuildResponse, BuildInstructionRequest, WrapSolRequest, UnwrapSolRequest, PriorityFeeResponse, AddressCheckResponse, StakeCreateRequest, StakeCreateResponse, AirdropRequest, AirdropResponse, TokenMetadataRequest, TokenMetadataResponse, RouteInfo, ServerPubkeyResponse, TokenMintCheckedRequest, KeypairInspectRequest, KeypairInspectResponse, TokenDelegation, TokenDelegationsResponse, ReadyResponse, DependencyStatus, TokenAccountRentResponse, BlockhashResponse, RentExemptResponse, RpcQueueMetrics, CreateAtaBatchRequest, AssociatedTokenAccountEntry, CreateAtaBatchResponse, FeatureFlags, ConfigResponse, Limits, VerifyMessagesBatchRequest, VerifyMessageResult, VerifyMessagesBatchResponse, KeypairExportRequest, KeypairExportResponse, AccountMetaInput, InstructionInput, TransactionAccountsRequest, TransactionAccountSummary, TransactionAccountsResponse, SolanaPayResponse, RentEstimateRequest, RentEstimateEntry, RentEstimateResponse, LeaderScheduleResponse, Ed25519InstructionRequest, MultipleAccountsRequest, AccountSummary, MultipleAccountsEntry, MultipleAccountsResponse, KeypairAirdropRequest, KeypairAirdropResponse, MemcmpBytesEncoding, MemcmpFilter, ProgramAccountsFilter, DataSlice, ProgramAccountsRequest, ProgramAccountEntry, ProgramAccountsResponse, MessageSignature, MultiSignMessageResponse, TokenProgramRequest, TokenProgramResponse, PartialSignRequest, PartialSignResponse, AccountDiffRequest, FieldChange, ByteRangeChange, AccountDiffResponse, TokenCreateImmutableResponse, DerivationPathRequest, DerivationPathResponse, PubkeyEncoding, ConvertPubkeyRequest, ConvertPubkeyResponse, ClusterPerformanceResponse, StakeMinimumResponse, TransferWithSeedRequest, GenesisResponse, BlockhashStatusRequest, BlockhashStatusResponse, TokenCloseMintRequest, TokenAccountInfoRequest, TokenAccountInfoResponse, MintInfoRequest, MintInfoResponse, SupplyResponse, KeypairFromSeedRequest, StakeAuthorizeKind, StakeAuthorizeRequest, MessageSelfTestRequest, MessageSelfTestResponse, StakeWithdrawRequest, StakeDeactivateRequest, SolanaPayUrlRequest, SolanaPayUrlResponse, SolanaPayParseRequest, SolanaPayRequestKind, SolanaPayParseResponse)),
     tags((name = "Solana API", description = "Solana balance and token endpoints"))
 )]
 pub struct ApiDoc;
+
+const METHODS: [&str; 5] = ["get", "post", "put", "patch", "delete"];
+
+// Flattens the spec into one entry per operation for `/routes`. Schemas are
+// left as they appear in the spec, usually `$ref`s into `components`.
+pub fn route_list() -> Vec<RouteInfo> {
+    let spec = serde_json::to_value(ApiDoc::openapi()).unwrap_or_default();
+    let Some(paths) = spec["paths"].as_object() else {
+        return Vec::new();
+    };
+
+    let mut routes = Vec::new();
+    for (path, item) in paths {
+        for method in METHODS {
+            let operation = &item[method];
+            if operation.is_null() {
+                continue;
+            }
+            let schema = |content: &serde_json::Value| {
+                let schema = &content["content"]["application/json"]["schema"];
+                (!schema.is_null()).then(|| schema.clone())
+            };
+            routes.push(RouteInfo {
+                path: path.clone(),
+                method: method.to_uppercase(),
+                request_schema: schema(&operation["requestBody"]),
+                response_schema: schema(&operation["responses"]["200"]),
+            });
+        }
+    }
+    routes
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn route_list_projects_request_and_response_schemas() {
+        let routes = route_list();
+        let send_sol = routes
+            .iter()
+            .find(|r| r.path == "/send/sol" && r.method == "POST")
+            .expect("/send/sol should be listed");
+        assert_eq!(
+            send_sol.request_schema.as_ref().unwrap()["$ref"],
+            "#/components/schemas/SendSolRequest"
+        );
+        assert!(send_sol.response_schema.is_some());
+
+        let balance = routes.iter().find(|r| r.path == "/balance/{address}").unwrap();
+        assert_eq!(balance.method, "GET");
+        assert!(balance.request_schema.is_none());
+    }
+}
@@ -0,0 +1,118 @@
+use crate::routes::ApiResponse;
+use axum::{
+    http::StatusCode,
+    response::IntoResponse,
+    routing::{any, MethodRouter},
+    Router,
+};
+
+// Routes the operator switched off with DISABLED_ROUTES. Their handlers are
+// never registered; the path answers with `status` instead, so a disabled
+// POST route still gets a clear error rather than the fallback's 405.
+pub struct DisabledRoutes {
+    paths: Vec<String>,
+    matched: Vec<String>,
+    status: StatusCode,
+}
+
+impl DisabledRoutes {
+    pub fn new(paths: Vec<String>, status: StatusCode) -> Self {
+        Self {
+            paths,
+            matched: Vec::new(),
+            status,
+        }
+    }
+
+    // DISABLED_ROUTES is a comma-separated list of paths exactly as they are
+    // registered (e.g. `/balance/{address}`). DISABLED_ROUTES_STATUS picks
+    // 404 (the default) or 403.
+    pub fn from_env() -> Result<Self, String> {
+        let paths = std::env::var("DISABLED_ROUTES")
+            .map(|v| {
+                v.split(',')
+                    .map(|s| s.trim().to_string())
+                    .filter(|s| !s.is_empty())
+                    .collect()
+            })
+            .unwrap_or_default();
+        let status = match std::env::var("DISABLED_ROUTES_STATUS").as_deref() {
+            Err(_) | Ok("404") => StatusCode::NOT_FOUND,
+            Ok("403") => StatusCode::FORBIDDEN,
+            Ok(other) => return Err(format!("DISABLED_ROUTES_STATUS must be 404 or 403, got {:?}", other)),
+        };
+        Ok(Self::new(paths, status))
+    }
+
+    pub fn paths(&self) -> &[String] {
+        &self.paths
+    }
+
+    // Entries that didn't match any route, which are almost certainly typos.
+    pub fn unmatched(&self) -> Vec<&str> {
+        self.paths
+            .iter()
+            .filter(|p| !self.matched.contains(p))
+            .map(String::as_str)
+            .collect()
+    }
+}
+
+pub trait RouterExt<S> {
+    // `Router::route`, unless `path` is in `disabled`.
+    fn route_enabled(self, disabled: &mut DisabledRoutes, path: &str, method_router: MethodRouter<S>) -> Self;
+}
+
+impl<S: Clone + Send + Sync + 'static> RouterExt<S> for Router<S> {
+    fn route_enabled(self, disabled: &mut DisabledRoutes, path: &str, method_router: MethodRouter<S>) -> Self {
+        if !disabled.paths.iter().any(|p| p == path) {
+            return self.route(path, method_router);
+        }
+
+        disabled.matched.push(path.to_string());
+        let status = disabled.status;
+        let error = format!("{} is disabled on this server", path);
+        self.route(
+            path,
+            any(move || async move {
+                (
+                    status,
+                    ApiResponse::<()>::Error {
+                        success: false,
+                        error,
+                    },
+                )
+                    .into_response()
+            }),
+        )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use axum::{body::Body, http::Request, routing::post};
+    use tower::ServiceExt;
+
+    #[tokio::test]
+    async fn disabled_route_returns_configured_status() {
+        let mut disabled = DisabledRoutes::new(
+            vec!["/airdrop".to_string(), "/typo".to_string()],
+            StatusCode::FORBIDDEN,
+        );
+        let app: Router = Router::new()
+            .route_enabled(&mut disabled, "/airdrop", post(|| async { "funded" }))
+            .route_enabled(&mut disabled, "/keypair", post(|| async { "keypair" }));
+        assert_eq!(disabled.unmatched(), vec!["/typo"]);
+
+        let request = |path: &str| Request::post(path).body(Body::empty()).unwrap();
+        let response = app.clone().oneshot(request("/airdrop")).await.unwrap();
+        assert_eq!(response.status(), StatusCode::FORBIDDEN);
+        let body = axum::body::to_bytes(response.into_body(), usize::MAX).await.unwrap();
+        let body: serde_json::Value = serde_json::from_slice(&body).unwrap();
+        assert_eq!(body["error"], "/airdrop is disabled on this server");
+
+        let response = app.oneshot(request("/keypair")).await.unwrap();
+        assert_eq!(response.status(), StatusCode::OK);
+    }
+}
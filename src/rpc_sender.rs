@@ -0,0 +1,372 @@
+use async_trait::async_trait;
+use serde::Serialize;
+use reqwest::{
+    header::{CONTENT_TYPE, RETRY_AFTER},
+    StatusCode,
+};
+use solana_client::{
+    client_error::{ClientError, ClientErrorKind, Result},
+    rpc_custom_error,
+    rpc_request::{RpcError, RpcRequest, RpcResponseErrorData},
+    rpc_response::RpcSimulateTransactionResult,
+    rpc_sender::{RpcSender, RpcTransportStats},
+};
+use std::{
+    sync::{
+        atomic::{AtomicU64, AtomicUsize, Ordering},
+        Arc, RwLock,
+    },
+    time::{Duration, Instant},
+};
+use tracing::Instrument;
+use tokio::sync::{Semaphore, SemaphorePermit};
+use utoipa::ToSchema;
+
+// Carried inside a ClientError when the upstream RPC answers 429, so the
+// handler can pass the rate limit (and its Retry-After) on to the client.
+#[derive(Debug)]
+pub struct RateLimited {
+    pub retry_after: Option<String>,
+}
+
+impl std::fmt::Display for RateLimited {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "upstream RPC rate limit exceeded")
+    }
+}
+
+impl std::error::Error for RateLimited {}
+
+pub fn rate_limited(err: &ClientError) -> Option<&RateLimited> {
+    match err.kind() {
+        ClientErrorKind::Io(io) => io.get_ref()?.downcast_ref::<RateLimited>(),
+        _ => None,
+    }
+}
+
+// Bounds how many RPC calls are in flight at once, across every client that
+// shares it, and keeps track of how long calls wait for a slot. Without a
+// limit calls never wait and the queue stays empty.
+pub struct RpcQueue {
+    permits: Option<Semaphore>,
+    max_concurrency: Option<usize>,
+    // Waits longer than this are logged.
+    warn_after: Duration,
+    waiting: AtomicUsize,
+    waits: AtomicU64,
+    total_wait_us: AtomicU64,
+    max_wait_us: AtomicU64,
+}
+
+#[derive(Debug, Serialize, ToSchema)]
+pub struct RpcQueueMetrics {
+    // Absent when RPC_MAX_CONCURRENCY is unset.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    max_concurrency: Option<usize>,
+    in_flight: usize,
+    queue_depth: usize,
+    // Calls that went through the limiter since startup.
+    waits: u64,
+    avg_wait_ms: f64,
+    max_wait_ms: f64,
+}
+
+impl Default for RpcQueue {
+    fn default() -> Self {
+        Self::new(None, Duration::from_secs(1))
+    }
+}
+
+// Counts a call as waiting until dropped, so a call cancelled mid-wait, by
+// the request deadline or a client disconnect, leaves the queue too.
+struct Waiting<'a>(&'a AtomicUsize);
+
+impl<'a> Waiting<'a> {
+    fn new(waiting: &'a AtomicUsize) -> Self {
+        waiting.fetch_add(1, Ordering::Relaxed);
+        Self(waiting)
+    }
+}
+
+impl Drop for Waiting<'_> {
+    fn drop(&mut self) {
+        self.0.fetch_sub(1, Ordering::Relaxed);
+    }
+}
+
+impl RpcQueue {
+    pub fn new(max_concurrency: Option<usize>, warn_after: Duration) -> Self {
+        Self {
+            permits: max_concurrency.map(Semaphore::new),
+            max_concurrency,
+            warn_after,
+            waiting: AtomicUsize::new(0),
+            waits: AtomicU64::new(0),
+            total_wait_us: AtomicU64::new(0),
+            max_wait_us: AtomicU64::new(0),
+        }
+    }
+
+    // Waits for a slot. The permit frees it again when dropped.
+    pub async fn acquire(&self) -> Option<SemaphorePermit<'_>> {
+        let permits = self.permits.as_ref()?;
+        let started = Instant::now();
+        let waiting = Waiting::new(&self.waiting);
+        let permit = permits.acquire().await.ok();
+        drop(waiting);
+
+        let waited = started.elapsed();
+        let waited_us = waited.as_micros() as u64;
+        self.waits.fetch_add(1, Ordering::Relaxed);
+        self.total_wait_us.fetch_add(waited_us, Ordering::Relaxed);
+        self.max_wait_us.fetch_max(waited_us, Ordering::Relaxed);
+        if waited > self.warn_after {
+            tracing::warn!(
+                wait_ms = waited.as_millis() as u64,
+                queue_depth = self.depth(),
+                "RPC call waited for a concurrency slot"
+            );
+        }
+        permit
+    }
+
+    pub fn max_concurrency(&self) -> Option<usize> {
+        self.max_concurrency
+    }
+
+    pub fn warn_after(&self) -> Duration {
+        self.warn_after
+    }
+
+    // Calls currently waiting for a slot.
+    pub fn depth(&self) -> usize {
+        self.waiting.load(Ordering::Relaxed)
+    }
+
+    pub fn metrics(&self) -> RpcQueueMetrics {
+        let waits = self.waits.load(Ordering::Relaxed);
+        let total_wait_us = self.total_wait_us.load(Ordering::Relaxed);
+        let in_flight = match (&self.permits, self.max_concurrency) {
+            (Some(permits), Some(max)) => max - permits.available_permits(),
+            _ => 0,
+        };
+        RpcQueueMetrics {
+            max_concurrency: self.max_concurrency,
+            in_flight,
+            queue_depth: self.depth(),
+            waits,
+            avg_wait_ms: if waits == 0 {
+                0.0
+            } else {
+                total_wait_us as f64 / waits as f64 / 1000.0
+            },
+            max_wait_ms: self.max_wait_us.load(Ordering::Relaxed) as f64 / 1000.0,
+        }
+    }
+}
+
+// A JSON-RPC sender equivalent to solana_client's HttpSender, except that a
+// 429 is surfaced immediately instead of being retried with sleeps inside
+// the request. Backing off is the caller's decision, and holding a request
+// open while we retry only makes the rate limiting worse.
+pub struct RateLimitAwareSender {
+    client: reqwest::Client,
+    url: String,
+    request_id: AtomicU64,
+    stats: RwLock<RpcTransportStats>,
+    queue: Arc<RpcQueue>,
+}
+
+impl RateLimitAwareSender {
+    pub fn new(url: String, queue: Arc<RpcQueue>) -> Self {
+        let timeout = Duration::from_secs(30);
+        Self {
+            client: reqwest::Client::builder()
+                .timeout(timeout)
+                .pool_idle_timeout(timeout)
+                .build()
+                .expect("build rpc client"),
+            url,
+            request_id: AtomicU64::new(0),
+            stats: RwLock::new(RpcTransportStats::default()),
+            queue,
+        }
+    }
+}
+
+#[async_trait]
+impl RpcSender for RateLimitAwareSender {
+    fn get_transport_stats(&self) -> RpcTransportStats {
+        self.stats.read().unwrap().clone()
+    }
+
+    async fn send(&self, request: RpcRequest, params: serde_json::Value) -> Result<serde_json::Value> {
+        let _permit = self.queue.acquire().await;
+        let started = Instant::now();
+        let request_id = self.request_id.fetch_add(1, Ordering::Relaxed);
+        let request_json = request.build_request_json(request_id, params).to_string();
+
+        let result = async {
+            let response = self
+                .client
+                .post(&self.url)
+                .header(CONTENT_TYPE, "application/json")
+                .body(request_json)
+                .send()
+                .await?;
+
+            if response.status() == StatusCode::TOO_MANY_REQUESTS {
+                let retry_after = response
+                    .headers()
+                    .get(RETRY_AFTER)
+                    .and_then(|v| v.to_str().ok())
+                    .map(str::to_string);
+                return Err(ClientError::from(std::io::Error::other(RateLimited {
+                    retry_after,
+                })));
+            }
+            if !response.status().is_success() {
+                return Err(response.error_for_status().unwrap_err().into());
+            }
+
+            let mut json = response.json::<serde_json::Value>().await?;
+            if json["error"].is_object() {
+                return Err(rpc_response_error(&json["error"]).into());
+            }
+            Ok(json["result"].take())
+        }
+        // Shows up under the request's span when traces are exported.
+        .instrument(tracing::info_span!("rpc", rpc.method = %request))
+        .await;
+
+        let mut stats = self.stats.write().unwrap();
+        stats.request_count += 1;
+        stats.elapsed_time += started.elapsed();
+        result
+    }
+
+    fn url(&self) -> String {
+        self.url.clone()
+    }
+}
+
+fn rpc_response_error(error: &serde_json::Value) -> RpcError {
+    let code = error["code"].as_i64().unwrap_or_default();
+    let message = error["message"].as_str().unwrap_or_default().to_string();
+
+    let data = match code {
+        rpc_custom_error::JSON_RPC_SERVER_ERROR_SEND_TRANSACTION_PREFLIGHT_FAILURE => {
+            serde_json::from_value::<RpcSimulateTransactionResult>(error["data"].clone())
+                .map(RpcResponseErrorData::SendTransactionPreflightFailure)
+                .unwrap_or(RpcResponseErrorData::Empty)
+        }
+        rpc_custom_error::JSON_RPC_SERVER_ERROR_NODE_UNHEALTHY => {
+            serde_json::from_value::<rpc_custom_error::NodeUnhealthyErrorData>(error["data"].clone())
+                .map(|data| RpcResponseErrorData::NodeUnhealthy {
+                    num_slots_behind: data.num_slots_behind,
+                })
+                .unwrap_or(RpcResponseErrorData::Empty)
+        }
+        _ => RpcResponseErrorData::Empty,
+    };
+
+    RpcError::RpcResponseError { code, message, data }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tokio::{
+        io::{AsyncReadExt, AsyncWriteExt},
+        net::TcpListener,
+    };
+
+    // Answers every connection with the given raw HTTP response.
+    async fn fake_rpc(response: String) -> String {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr().unwrap();
+        tokio::spawn(async move {
+            while let Ok((mut socket, _)) = listener.accept().await {
+                let mut buf = [0u8; 4096];
+                let _ = socket.read(&mut buf).await;
+                let _ = socket.write_all(response.as_bytes()).await;
+            }
+        });
+        format!("http://{}", addr)
+    }
+
+    #[tokio::test]
+    async fn upstream_429_carries_retry_after() {
+        let url = fake_rpc(
+            "HTTP/1.1 429 Too Many Requests\r\nRetry-After: 7\r\nContent-Length: 0\r\nConnection: close\r\n\r\n"
+                .to_string(),
+        )
+        .await;
+        let sender = RateLimitAwareSender::new(url, Default::default());
+
+        let err = sender
+            .send(RpcRequest::GetSlot, serde_json::Value::Null)
+            .await
+            .unwrap_err();
+
+        let limited = rate_limited(&err).expect("429 should be reported as rate limited");
+        assert_eq!(limited.retry_after.as_deref(), Some("7"));
+    }
+
+    #[tokio::test]
+    async fn json_rpc_errors_are_not_rate_limits() {
+        let body = r#"{"jsonrpc":"2.0","error":{"code":-32601,"message":"Method not found"},"id":0}"#;
+        let response = format!(
+            "HTTP/1.1 200 OK\r\nContent-Type: application/json\r\nContent-Length: {}\r\nConnection: close\r\n\r\n{}",
+            body.len(),
+            body
+        );
+        let sender = RateLimitAwareSender::new(fake_rpc(response).await, Default::default());
+
+        let err = sender
+            .send(RpcRequest::GetSlot, serde_json::Value::Null)
+            .await
+            .unwrap_err();
+
+        assert!(rate_limited(&err).is_none());
+        assert!(matches!(
+            err.kind(),
+            ClientErrorKind::RpcError(RpcError::RpcResponseError { code: -32601, .. })
+        ));
+    }
+
+    #[tokio::test]
+    async fn queue_tracks_waiting_calls() {
+        let queue = Arc::new(RpcQueue::new(Some(1), Duration::from_secs(1)));
+        let held = queue.acquire().await;
+        assert_eq!(queue.metrics().in_flight, 1);
+
+        let waiter = tokio::spawn({
+            let queue = queue.clone();
+            async move {
+                let _permit = queue.acquire().await;
+            }
+        });
+        while queue.depth() == 0 {
+            tokio::task::yield_now().await;
+        }
+        assert_eq!(queue.metrics().queue_depth, 1);
+
+        drop(held);
+        waiter.await.unwrap();
+        let metrics = queue.metrics();
+        assert_eq!(metrics.queue_depth, 0);
+        assert_eq!(metrics.in_flight, 0);
+        assert_eq!(metrics.waits, 2);
+    }
+
+    #[tokio::test]
+    async fn cancelled_waits_leave_the_queue() {
+        let queue = RpcQueue::new(Some(1), Duration::from_secs(1));
+        let _held = queue.acquire().await;
+
+        let timed_out = tokio::time::timeout(Duration::from_millis(10), queue.acquire()).await;
+        assert!(timed_out.is_err());
+        assert_eq!(queue.depth(), 0);
+    }
+}
@@ -0,0 +1,314 @@
+use crate::{
+    airdrop::AirdropTracker, cache::{BalanceCache, BlockhashCache, ValueCache, STAKE_MINIMUM_TTL, SUPPLY_TTL}, health::ReadinessChecks,
+    routes::keypair_from_base58_secret,
+    rpc_sender::{RateLimitAwareSender, RpcQueue},
+};
+use solana_client::{nonblocking::rpc_client::RpcClient, rpc_client::RpcClientConfig, rpc_response::RpcSupply};
+use serde::Serialize;
+use solana_sdk::signature::Keypair;
+use std::{sync::Arc, time::Duration};
+use utoipa::ToSchema;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Cluster {
+    MainnetBeta,
+    Devnet,
+    Testnet,
+    Localnet,
+    Unknown,
+}
+
+impl Cluster {
+    // SOLANA_CLUSTER takes precedence; otherwise we guess from the RPC URL,
+    // which covers the public endpoints and most provider URLs.
+    fn detect(rpc_url: &str) -> Self {
+        let hint = std::env::var("SOLANA_CLUSTER").unwrap_or_else(|_| rpc_url.to_string());
+        let hint = hint.to_lowercase();
+
+        if hint.contains("mainnet") {
+            Cluster::MainnetBeta
+        } else if hint.contains("devnet") {
+            Cluster::Devnet
+        } else if hint.contains("testnet") {
+            Cluster::Testnet
+        } else if hint.contains("localhost") || hint.contains("127.0.0.1") || hint == "localnet" {
+            Cluster::Localnet
+        } else {
+            Cluster::Unknown
+        }
+    }
+
+    // The public clusters by genesis hash, which unlike the RPC URL can't be
+    // mislabelled. Local validators get a fresh hash on every reset.
+    pub fn from_genesis_hash(hash: &str) -> Option<Self> {
+        match hash {
+            "5eykt4UsFv8P8NJdTREpY1vzqKqZKvdpKuc147dw2N9d" => Some(Cluster::MainnetBeta),
+            "EtWTRABZaYq6iMfeYKouRu166VU2xqa1wcaWoxPkrZBG" => Some(Cluster::Devnet),
+            "4uhcVJyU9pJkvQyS88uRDiswHXSCkY3zQawwpjk2NsNY" => Some(Cluster::Testnet),
+            _ => None,
+        }
+    }
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            Cluster::MainnetBeta => "mainnet-beta",
+            Cluster::Devnet => "devnet",
+            Cluster::Testnet => "testnet",
+            Cluster::Localnet => "localnet",
+            Cluster::Unknown => "unknown",
+        }
+    }
+}
+
+// Operator-set caps on what the builders will produce. The defaults leave
+// amounts unbounded and keep batches within a single legacy transaction.
+#[derive(Debug, Clone, Copy, Serialize, ToSchema)]
+pub struct Limits {
+    pub max_batch_recipients: usize,
+    pub max_transfer_lamports: u64,
+    pub max_mint_amount: u64,
+    pub max_message_bytes: usize,
+    pub max_instructions_per_tx: usize,
+}
+
+impl Default for Limits {
+    fn default() -> Self {
+        Self {
+            max_batch_recipients: 20,
+            max_transfer_lamports: u64::MAX,
+            max_mint_amount: u64::MAX,
+            max_message_bytes: 64 * 1024,
+            // The runtime rejects transactions with more instructions than
+            // this, counting CPIs, so nothing larger could ever execute.
+            max_instructions_per_tx: 64,
+        }
+    }
+}
+
+impl Limits {
+    fn from_env() -> Result<Self, String> {
+        let defaults = Self::default();
+        Ok(Self {
+            max_batch_recipients: env_or("MAX_BATCH_RECIPIENTS", defaults.max_batch_recipients)?,
+            max_transfer_lamports: env_or("MAX_TRANSFER_LAMPORTS", defaults.max_transfer_lamports)?,
+            max_mint_amount: env_or("MAX_MINT_AMOUNT", defaults.max_mint_amount)?,
+            max_message_bytes: env_or("MAX_MESSAGE_BYTES", defaults.max_message_bytes)?,
+            max_instructions_per_tx: env_or("MAX_INSTRUCTIONS_PER_TX", defaults.max_instructions_per_tx)?,
+        })
+    }
+}
+
+// The default only stands in for an unset variable. A value that doesn't
+// parse, such as `1_000_000` or `5e9` for a limit, is an error rather than
+// quietly replaced by the default.
+fn env_or<T: std::str::FromStr>(name: &str, default: T) -> Result<T, String> {
+    match std::env::var(name) {
+        Ok(value) => value
+            .trim()
+            .parse()
+            .map_err(|_| format!("Invalid {}: {:?} is not a valid value", name, value)),
+        Err(_) => Ok(default),
+    }
+}
+
+// Startup settings refuse to start the server when they're wrong.
+fn or_exit<T>(result: Result<T, String>) -> T {
+    result.unwrap_or_else(|e| {
+        tracing::error!("{}", e);
+        std::process::exit(1);
+    })
+}
+
+// Shared state handed to every handler. The RPC client keeps a connection
+// pool internally, so we build it once at startup instead of per request.
+#[derive(Clone)]
+pub struct AppState {
+    // Serves reads. Writes (currently just airdrops) go through `write_rpc`,
+    // which is the same client unless SOLANA_RPC_URL_WRITE differs.
+    pub rpc: Arc<RpcClient>,
+    pub write_rpc: Arc<RpcClient>,
+    // Shared by both clients, so RPC_MAX_CONCURRENCY bounds them together.
+    pub rpc_queue: Arc<RpcQueue>,
+    // With RPC_QUEUE_DEPTH_HEADER=true, responses carry X-RPC-Queue-Depth.
+    pub rpc_queue_header: bool,
+    pub cluster: Cluster,
+    // Only present when BALANCE_CACHE_TTL_MS is set to a non-zero value.
+    pub balance_cache: Option<Arc<BalanceCache>>,
+    // Latest blockhash, kept for BLOCKHASH_CACHE_TTL_MS (default 2000, 0 disables).
+    pub blockhash_cache: Option<Arc<BlockhashCache>>,
+    pub stake_minimum_cache: Arc<ValueCache<u64>>,
+    pub supply_cache: Arc<ValueCache<RpcSupply>>,
+    // When set, mainnet requests to the transfer/mint builders must carry
+    // `confirmMainnet: true`.
+    pub safe_mode: bool,
+    // When set, token transfers and mints must include `decimals`.
+    pub require_checked_transfers: bool,
+    // Upper bound on the deadline a client can ask for via X-Request-Timeout-Ms.
+    pub max_request_timeout: Duration,
+    pub limits: Limits,
+    // Fraction of successful requests the access log records; errors are
+    // always logged.
+    pub log_sample_rate: f64,
+    pub airdrops: Arc<AirdropTracker>,
+    // Set from SERVER_SIGNING_SECRET; responses carry an X-Signature by it.
+    pub signing_key: Option<Arc<Keypair>>,
+    // Dependencies /ready reports on.
+    pub readiness: Arc<ReadinessChecks>,
+    // Paths switched off with DISABLED_ROUTES, which /routes leaves out.
+    pub disabled_routes: Arc<Vec<String>>,
+}
+
+impl AppState {
+    pub fn from_env() -> Self {
+        let rpc_url = std::env::var("SOLANA_RPC_URL")
+            .unwrap_or_else(|_| "https://api.mainnet-beta.solana.com".to_string());
+        let read_url = std::env::var("SOLANA_RPC_URL_READ").unwrap_or_else(|_| rpc_url.clone());
+        let write_url = std::env::var("SOLANA_RPC_URL_WRITE").unwrap_or(rpc_url);
+        // RpcClient accepts any string and only fails once a request is sent,
+        // so a typo here would otherwise surface as a 502 on every call. The
+        // URL itself stays out of the log, since it often carries an API key.
+        let url_checks = [("SOLANA_RPC_URL_READ", &read_url), ("SOLANA_RPC_URL_WRITE", &write_url)];
+        for (name, url) in url_checks {
+            if let Err(e) = validate_rpc_url(url) {
+                // Both fall back to SOLANA_RPC_URL, so name whichever is set.
+                let name = if std::env::var(name).is_ok() { name } else { "SOLANA_RPC_URL" };
+                tracing::error!("Invalid {}: {}", name, e);
+                std::process::exit(1);
+            }
+        }
+        let cluster = Cluster::detect(&read_url);
+
+        let rpc_queue = Arc::new(RpcQueue::new(
+            Some(or_exit(env_or("RPC_MAX_CONCURRENCY", 0_usize))).filter(|n| *n > 0),
+            Duration::from_millis(or_exit(env_or("RPC_QUEUE_WARN_MS", 1000))),
+        ));
+        let rpc = rpc_client(read_url.clone(), &rpc_queue);
+        let write_rpc = if write_url == read_url {
+            rpc.clone()
+        } else {
+            rpc_client(write_url, &rpc_queue)
+        };
+
+        let balance_cache = std::env::var("BALANCE_CACHE_TTL_MS")
+            .ok()
+            .and_then(|ttl| ttl.parse::<u64>().ok())
+            .filter(|ttl| *ttl > 0)
+            .map(|ttl| Arc::new(BalanceCache::new(Duration::from_millis(ttl))));
+
+        let blockhash_cache = Some(or_exit(env_or("BLOCKHASH_CACHE_TTL_MS", 2000_u64)))
+            .filter(|ttl| *ttl > 0)
+            .map(|ttl| Arc::new(BlockhashCache::new(Duration::from_millis(ttl))));
+
+        let safe_mode = std::env::var("SAFE_MODE")
+            .map(|v| v.eq_ignore_ascii_case("true"))
+            .unwrap_or(false);
+
+        let require_checked_transfers = std::env::var("REQUIRE_CHECKED_TRANSFERS")
+            .map(|v| v.eq_ignore_ascii_case("true"))
+            .unwrap_or(false);
+
+        let max_request_timeout = std::env::var("MAX_REQUEST_TIMEOUT_MS")
+            .ok()
+            .and_then(|ms| ms.parse::<u64>().ok())
+            .map(Duration::from_millis)
+            .unwrap_or(Duration::from_secs(30));
+
+        // A typo here would silently turn signing off, so refuse to start.
+        let signing_key = std::env::var("SERVER_SIGNING_SECRET").ok().map(|secret| {
+            match keypair_from_base58_secret(&secret) {
+                Ok(keypair) => Arc::new(keypair),
+                Err(e) => {
+                    tracing::error!("Invalid SERVER_SIGNING_SECRET: {}", e);
+                    std::process::exit(1);
+                }
+            }
+        });
+
+        let readiness = match ReadinessChecks::from_env(&rpc, &write_rpc) {
+            Ok(checks) => Arc::new(checks),
+            Err(e) => {
+                tracing::error!("Invalid readiness configuration: {}", e);
+                std::process::exit(1);
+            }
+        };
+
+        Self {
+            rpc,
+            write_rpc,
+            rpc_queue,
+            rpc_queue_header: or_exit(env_or("RPC_QUEUE_DEPTH_HEADER", false)),
+            cluster,
+            balance_cache,
+            blockhash_cache,
+            stake_minimum_cache: Arc::new(ValueCache::new(STAKE_MINIMUM_TTL)),
+            supply_cache: Arc::new(ValueCache::new(SUPPLY_TTL)),
+            safe_mode,
+            require_checked_transfers,
+            max_request_timeout,
+            limits: or_exit(Limits::from_env()),
+            log_sample_rate: or_exit(env_or("LOG_SAMPLE_RATE", 0.0_f64)).clamp(0.0, 1.0),
+            airdrops: Arc::new(AirdropTracker::default()),
+            signing_key,
+            readiness,
+            disabled_routes: Default::default(),
+        }
+    }
+}
+
+fn validate_rpc_url(url: &str) -> Result<(), String> {
+    let parsed = reqwest::Url::parse(url).map_err(|e| format!("not a valid URL ({})", e))?;
+    if !matches!(parsed.scheme(), "http" | "https") {
+        return Err(format!("must use http or https, not {}", parsed.scheme()));
+    }
+    if parsed.host_str().is_none_or(str::is_empty) {
+        return Err("has no host".to_string());
+    }
+    Ok(())
+}
+
+fn rpc_client(url: String, queue: &Arc<RpcQueue>) -> Arc<RpcClient> {
+    Arc::new(RpcClient::new_sender(
+        RateLimitAwareSender::new(url, queue.clone()),
+        RpcClientConfig::default(),
+    ))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn validate_rpc_url_requires_http_and_a_host() {
+        for url in [
+            "https://api.mainnet-beta.solana.com",
+            "http://127.0.0.1:8899",
+            "https://rpc.example.com/v1/abc123?key=x",
+        ] {
+            assert_eq!(validate_rpc_url(url), Ok(()), "{}", url);
+        }
+
+        assert!(validate_rpc_url("api.mainnet-beta.solana.com").unwrap_err().contains("not a valid URL"));
+        assert!(validate_rpc_url("").unwrap_err().contains("not a valid URL"));
+        assert!(validate_rpc_url("http://").unwrap_err().contains("not a valid URL"));
+        assert_eq!(
+            validate_rpc_url("wss://api.mainnet-beta.solana.com").unwrap_err(),
+            "must use http or https, not wss"
+        );
+        assert!(validate_rpc_url("file:///tmp/rpc").unwrap_err().contains("must use http or https"));
+    }
+
+    #[test]
+    fn env_or_rejects_values_that_do_not_parse() {
+        // Names no other test reads, since tests share the environment.
+        assert_eq!(env_or("ENV_OR_TEST_UNSET", 7_u64), Ok(7));
+        std::env::set_var("ENV_OR_TEST_VALID", "1000000");
+        assert_eq!(env_or("ENV_OR_TEST_VALID", 7_u64), Ok(1_000_000));
+        for value in ["1_000_000", "5e9", ""] {
+            std::env::set_var("ENV_OR_TEST_INVALID", value);
+            assert_eq!(
+                env_or("ENV_OR_TEST_INVALID", 7_u64),
+                Err(format!("Invalid ENV_OR_TEST_INVALID: {:?} is not a valid value", value))
+            );
+        }
+    }
+}
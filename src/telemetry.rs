@@ -0,0 +1,96 @@
+use axum::{extract::MatchedPath, extract::Request, http::HeaderMap, middleware::Next};
+use opentelemetry::{
+    propagation::Extractor,
+    trace::{TraceError, TracerProvider as _},
+    KeyValue,
+};
+use opentelemetry_sdk::{propagation::TraceContextPropagator, trace::TracerProvider, Resource};
+use std::sync::atomic::{AtomicBool, Ordering};
+use tracing::{field::Empty, Instrument};
+use tracing_opentelemetry::OpenTelemetrySpanExt;
+use tracing_subscriber::{filter::LevelFilter, layer::SubscriberExt, util::SubscriberInitExt};
+
+// Set once export is configured; until then `request_span` is a no-op, so
+// log lines look exactly as they did without tracing.
+static EXPORTING: AtomicBool = AtomicBool::new(false);
+
+// Sets up logging, plus OTLP trace export when OTEL_EXPORTER_OTLP_ENDPOINT
+// is set. Sampling follows the standard OTEL_TRACES_SAMPLER and
+// OTEL_TRACES_SAMPLER_ARG variables, which the SDK reads itself.
+pub fn init() {
+    if std::env::var("OTEL_EXPORTER_OTLP_ENDPOINT").is_err() {
+        tracing_subscriber::fmt::init();
+        return;
+    }
+
+    match tracer_provider() {
+        Ok(provider) => {
+            let tracer = provider.tracer("sol_rs_server");
+            opentelemetry::global::set_tracer_provider(provider);
+            opentelemetry::global::set_text_map_propagator(TraceContextPropagator::new());
+            tracing_subscriber::registry()
+                .with(LevelFilter::INFO)
+                .with(tracing_subscriber::fmt::layer())
+                .with(tracing_opentelemetry::layer().with_tracer(tracer))
+                .init();
+            EXPORTING.store(true, Ordering::Relaxed);
+        }
+        // Tracing was asked for, so running without it would go unnoticed.
+        Err(e) => {
+            tracing_subscriber::fmt::init();
+            tracing::error!("Failed to set up OTLP trace export: {}", e);
+            std::process::exit(1);
+        }
+    }
+}
+
+fn tracer_provider() -> Result<TracerProvider, TraceError> {
+    // The exporter reads OTEL_EXPORTER_OTLP_ENDPOINT and appends /v1/traces.
+    let exporter = opentelemetry_otlp::SpanExporter::builder().with_http().build()?;
+    let service_name = std::env::var("OTEL_SERVICE_NAME").unwrap_or_else(|_| "sol_rs_server".to_string());
+    Ok(TracerProvider::builder()
+        .with_batch_exporter(exporter, opentelemetry_sdk::runtime::Tokio)
+        .with_resource(Resource::new([KeyValue::new("service.name", service_name)]))
+        .build())
+}
+
+struct HeaderExtractor<'a>(&'a HeaderMap);
+
+impl Extractor for HeaderExtractor<'_> {
+    fn get(&self, key: &str) -> Option<&str> {
+        self.0.get(key).and_then(|v| v.to_str().ok())
+    }
+
+    fn keys(&self) -> Vec<&str> {
+        self.0.keys().map(|k| k.as_str()).collect()
+    }
+}
+
+// One span per request, named after the route template rather than the raw
+// path so `/balance/{address}` doesn't fan out per address. A `traceparent`
+// header makes it a child of the caller's trace. Without an exporter the
+// middleware does nothing.
+pub async fn request_span(req: Request, next: Next) -> axum::response::Response {
+    if !EXPORTING.load(Ordering::Relaxed) {
+        return next.run(req).await;
+    }
+    let route = req
+        .extensions()
+        .get::<MatchedPath>()
+        .map_or_else(|| req.uri().path().to_string(), |p| p.as_str().to_string());
+    let span = tracing::info_span!(
+        "request",
+        otel.name = format!("{} {}", req.method(), route),
+        http.request.method = %req.method(),
+        http.route = route,
+        http.response.status_code = Empty,
+    );
+    let parent = opentelemetry::global::get_text_map_propagator(|propagator| {
+        propagator.extract(&HeaderExtractor(req.headers()))
+    });
+    span.set_parent(parent);
+
+    let response = next.run(req).instrument(span.clone()).await;
+    span.record("http.response.status_code", response.status().as_u16());
+    response
+}
@@ -0,0 +1,216 @@
+use std::{fs::File, io, io::BufReader, net::SocketAddr, sync::Arc, time::Duration};
+use tokio::{
+    net::{TcpListener, TcpStream},
+    sync::mpsc,
+};
+use tokio_rustls::{
+    rustls::{
+        version::TLS13,
+        Certificate, PrivateKey, ServerConfig, SupportedCipherSuite, SupportedProtocolVersion,
+        ALL_CIPHER_SUITES, ALL_VERSIONS, DEFAULT_CIPHER_SUITES,
+    },
+    server::TlsStream,
+    TlsAcceptor,
+};
+
+// A client that connects and never finishes the handshake shouldn't hold a
+// task forever.
+const HANDSHAKE_TIMEOUT: Duration = Duration::from_secs(10);
+
+static TLS13_ONLY: &[&SupportedProtocolVersion] = &[&TLS13];
+
+// Protocol versions and cipher suites the server offers. The defaults are
+// rustls's own: TLS 1.2 and 1.3 with its recommended suites.
+#[derive(Debug)]
+pub struct TlsSettings {
+    versions: &'static [&'static SupportedProtocolVersion],
+    cipher_suites: Vec<SupportedCipherSuite>,
+}
+
+impl TlsSettings {
+    // TLS_MIN_VERSION is `1.2` (the default) or `1.3`. TLS_CIPHER_SUITES is a
+    // comma-separated list of IANA suite names, e.g.
+    // `TLS13_AES_256_GCM_SHA384,TLS_ECDHE_RSA_WITH_AES_256_GCM_SHA384`.
+    pub fn from_env() -> Result<Self, String> {
+        Self::parse(
+            std::env::var("TLS_MIN_VERSION").ok().as_deref(),
+            std::env::var("TLS_CIPHER_SUITES").ok().as_deref(),
+        )
+    }
+
+    fn parse(min_version: Option<&str>, cipher_suites: Option<&str>) -> Result<Self, String> {
+        let versions: &'static [&'static SupportedProtocolVersion] = match min_version.map(str::trim) {
+            None | Some("1.2") => ALL_VERSIONS,
+            Some("1.3") => TLS13_ONLY,
+            Some(other) => return Err(format!("TLS_MIN_VERSION must be 1.2 or 1.3, got {:?}", other)),
+        };
+
+        let cipher_suites = match cipher_suites {
+            None => DEFAULT_CIPHER_SUITES.to_vec(),
+            Some(names) => names
+                .split(',')
+                .map(str::trim)
+                .filter(|name| !name.is_empty())
+                .map(cipher_suite)
+                .collect::<Result<Vec<_>, _>>()?,
+        };
+        // A suite only works with its own protocol version, so a list of 1.2
+        // suites under a 1.3 minimum would leave nothing to negotiate.
+        if !cipher_suites
+            .iter()
+            .any(|suite| versions.iter().any(|v| suite.version() == *v))
+        {
+            return Err(match min_version {
+                Some(version) => format!("TLS_CIPHER_SUITES has no suites usable with TLS {}", version.trim()),
+                None => "TLS_CIPHER_SUITES must name at least one cipher suite".to_string(),
+            });
+        }
+
+        Ok(Self { versions, cipher_suites })
+    }
+}
+
+fn cipher_suite(name: &str) -> Result<SupportedCipherSuite, String> {
+    let suite_name = |suite: &SupportedCipherSuite| format!("{:?}", suite.suite());
+    ALL_CIPHER_SUITES
+        .iter()
+        .find(|suite| suite_name(suite).eq_ignore_ascii_case(name))
+        .copied()
+        .ok_or_else(|| {
+            let supported: Vec<String> = ALL_CIPHER_SUITES.iter().map(suite_name).collect();
+            format!("Unsupported cipher suite {:?}; supported suites are {}", name, supported.join(", "))
+        })
+}
+
+// Loads a PEM certificate chain and private key (PKCS#8, RSA, or SEC1).
+pub fn load_config(cert_path: &str, key_path: &str, settings: &TlsSettings) -> Result<Arc<ServerConfig>, String> {
+    let certs = rustls_pemfile::certs(&mut open(cert_path)?)
+        .map_err(|e| format!("Failed to parse {}: {}", cert_path, e))?;
+    if certs.is_empty() {
+        return Err(format!("No certificates found in {}", cert_path));
+    }
+
+    let key = rustls_pemfile::read_all(&mut open(key_path)?)
+        .map_err(|e| format!("Failed to parse {}: {}", key_path, e))?
+        .into_iter()
+        .find_map(|item| match item {
+            rustls_pemfile::Item::PKCS8Key(key)
+            | rustls_pemfile::Item::RSAKey(key)
+            | rustls_pemfile::Item::ECKey(key) => Some(key),
+            _ => None,
+        })
+        .ok_or_else(|| format!("No private key found in {}", key_path))?;
+
+    let config = ServerConfig::builder()
+        .with_cipher_suites(&settings.cipher_suites)
+        .with_safe_default_kx_groups()
+        .with_protocol_versions(settings.versions)
+        .map_err(|e| format!("Invalid TLS settings: {}", e))?
+        .with_no_client_auth()
+        .with_single_cert(certs.into_iter().map(Certificate).collect(), PrivateKey(key))
+        .map_err(|e| format!("Invalid certificate or key: {}", e))?;
+    Ok(Arc::new(config))
+}
+
+fn open(path: &str) -> Result<BufReader<File>, String> {
+    File::open(path)
+        .map(BufReader::new)
+        .map_err(|e| format!("Failed to open {}: {}", path, e))
+}
+
+// Accepts TCP connections and hands axum only the ones that complete a TLS
+// handshake. Handshakes run on their own tasks so a slow client can't stall
+// the accept loop.
+pub struct TlsListener {
+    streams: mpsc::Receiver<(TlsStream<TcpStream>, SocketAddr)>,
+    local_addr: SocketAddr,
+}
+
+impl TlsListener {
+    pub fn new(listener: TcpListener, config: Arc<ServerConfig>) -> io::Result<Self> {
+        let local_addr = listener.local_addr()?;
+        let acceptor = TlsAcceptor::from(config);
+        let (tx, streams) = mpsc::channel(64);
+
+        tokio::spawn(async move {
+            loop {
+                let (stream, addr) = match listener.accept().await {
+                    Ok(conn) => conn,
+                    Err(e) => {
+                        // Usually fd exhaustion; back off instead of spinning.
+                        tracing::warn!("Failed to accept connection: {}", e);
+                        tokio::time::sleep(Duration::from_millis(100)).await;
+                        continue;
+                    }
+                };
+
+                let acceptor = acceptor.clone();
+                let tx = tx.clone();
+                tokio::spawn(async move {
+                    match tokio::time::timeout(HANDSHAKE_TIMEOUT, acceptor.accept(stream)).await {
+                        Ok(Ok(stream)) => {
+                            let _ = tx.send((stream, addr)).await;
+                        }
+                        Ok(Err(e)) => tracing::debug!("TLS handshake with {} failed: {}", addr, e),
+                        Err(_) => tracing::debug!("TLS handshake with {} timed out", addr),
+                    }
+                });
+            }
+        });
+
+        Ok(Self { streams, local_addr })
+    }
+}
+
+impl axum::serve::Listener for TlsListener {
+    type Io = TlsStream<TcpStream>;
+    type Addr = SocketAddr;
+
+    async fn accept(&mut self) -> (Self::Io, Self::Addr) {
+        self.streams
+            .recv()
+            .await
+            .expect("the accept loop runs for the lifetime of the listener")
+    }
+
+    fn local_addr(&self) -> io::Result<Self::Addr> {
+        Ok(self.local_addr)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn tls_settings_parse_versions_and_suites() {
+        let settings = TlsSettings::parse(None, None).unwrap();
+        assert_eq!(settings.versions.len(), 2);
+        assert_eq!(settings.cipher_suites.len(), DEFAULT_CIPHER_SUITES.len());
+
+        let settings = TlsSettings::parse(
+            Some("1.3"),
+            Some("tls13_aes_256_gcm_sha384, TLS13_CHACHA20_POLY1305_SHA256"),
+        )
+        .unwrap();
+        assert_eq!(settings.versions, &[&TLS13]);
+        assert_eq!(settings.cipher_suites.len(), 2);
+        assert!(settings.cipher_suites.iter().all(|suite| suite.version() == &TLS13));
+
+        assert_eq!(
+            TlsSettings::parse(Some("1.1"), None).unwrap_err(),
+            "TLS_MIN_VERSION must be 1.2 or 1.3, got \"1.1\""
+        );
+        assert!(TlsSettings::parse(None, Some("TLS_RSA_WITH_RC4_128_SHA"))
+            .unwrap_err()
+            .starts_with("Unsupported cipher suite \"TLS_RSA_WITH_RC4_128_SHA\"; supported suites are TLS13_"));
+        assert_eq!(
+            TlsSettings::parse(Some("1.3"), Some("TLS_ECDHE_RSA_WITH_AES_256_GCM_SHA384")).unwrap_err(),
+            "TLS_CIPHER_SUITES has no suites usable with TLS 1.3"
+        );
+        assert_eq!(
+            TlsSettings::parse(None, Some(" , ")).unwrap_err(),
+            "TLS_CIPHER_SUITES must name at least one cipher suite"
+        );
+    }
+}
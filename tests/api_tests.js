@@ -1,6 +1,6 @@
 const axios = require("axios");
 const nacl = require('tweetnacl');
-const { PublicKey, Keypair } = require('@solana/web3.js');
+const { PublicKey, Keypair, Transaction } = require('@solana/web3.js');
 const bs58 = require('bs58').default;
 const { getAssociatedTokenAddress } = require("@solana/spl-token");
 
@@ -12,6 +12,11 @@ const SUCCESS_CODE = 200;
 
 const TOKEN_PROGRAM_ID = "TokenkegQfeZyiNwAJbNbGKPFXCWuBvf9Ss623VQ5DA";
 
+// Some behaviour depends on how the server under test was started. These
+// mirror the server's env so the matching tests only run when they apply.
+const SAFE_MODE_MAINNET = process.env.SAFE_MODE === "true" && process.env.SOLANA_CLUSTER === "mainnet-beta";
+const testIf = (condition) => (condition ? test : test.skip);
+
 describe("Solana Fellowship API", () => {
   let generatedKeypair = null;
 
@@ -111,7 +116,23 @@ describe("Solana Fellowship API", () => {
     expect(res.status).toBe(400);
     expect(res.data.success).toBe(false);
     expect(res.data.error).toBeDefined()
-    
+
+  });
+
+  test("POST /token/create should return a mapped error for out-of-range decimals", async () => {
+    let mintKeypair = Keypair.generate();
+    const res = await axios.post(`${HTTP_URL}/token/create`, {
+      mintAuthority: generatedKeypair.pubkey,
+      mint: mintKeypair.publicKey,
+      decimals: 10
+    }, {
+      validateStatus: () => true // Don't throw on any status code
+    });
+
+    expect(res.status).toBe(400);
+    expect(res.data.success).toBe(false);
+    expect(res.data.error).toBe("Decimals must be between 0 and 9");
+    expect(res.data.code).toBe("INVALID_DECIMALS");
   });
 
   test("POST /token/mint should return valid mint_to instruction", async () => {
@@ -459,4 +480,221 @@ describe("Solana Fellowship API", () => {
     expect(res.status).toBe(400);
     expect(res.data.success).toBe(false);
   });
+
+  test("POST /pda/find should match web3.js program address derivation", async () => {
+    const programId = Keypair.generate().publicKey;
+    const res = await axios.post(`${HTTP_URL}/pda/find`, {
+      programId: programId.toString(),
+      seeds: ["vault", "6869"],
+    });
+
+    expect(res.status).toBe(SUCCESS_CODE);
+    expect(res.data.success).toBe(true);
+
+    const [expected, bump] = PublicKey.findProgramAddressSync(
+      [Buffer.from("vault"), Buffer.from("6869")],
+      programId
+    );
+    expect(res.data.data.address).toBe(expected.toString());
+    expect(res.data.data.bump).toBe(bump);
+
+    const hexRes = await axios.post(`${HTTP_URL}/pda/find`, {
+      programId: programId.toString(),
+      seeds: ["7661756c74"],
+      seedEncoding: "hex",
+    });
+    const [hexExpected] = PublicKey.findProgramAddressSync([Buffer.from("vault")], programId);
+    expect(hexRes.data.data.address).toBe(hexExpected.toString());
+  });
+
+  test("POST /pda/find should reject seeds longer than 32 bytes", async () => {
+    const res = await axios.post(`${HTTP_URL}/pda/find`, {
+      programId: Keypair.generate().publicKey.toString(),
+      seeds: ["a".repeat(33)],
+    }, {
+      validateStatus: () => true // Don't throw on any status code
+    });
+
+    expect(res.status).toBe(ERROR_CODE);
+    expect(res.data.success).toBe(false);
+  });
+
+  test("POST /send/sol?dryRun=true should build offline and mark the response", async () => {
+    const senderKeypair = Keypair.generate();
+    const recipientKeypair = Keypair.generate();
+    const body = {
+      from: senderKeypair.publicKey.toString(),
+      to: recipientKeypair.publicKey.toString(),
+      lamports: 5000,
+    };
+
+    const [online, offline] = await Promise.all([
+      axios.post(`${HTTP_URL}/send/sol`, body),
+      axios.post(`${HTTP_URL}/send/sol?dryRun=true`, body),
+    ]);
+
+    expect(offline.status).toBe(SUCCESS_CODE);
+    expect(offline.data.data.dry_run).toBe(true);
+    expect(offline.data.data.omitted).toEqual([]);
+    expect(offline.data.data.instruction_data).toBe(online.data.data.instruction_data);
+    expect(online.data.data.dry_run).not.toBeDefined();
+  });
+
+  test("POST /send/sol/batch should return one transfer per recipient", async () => {
+    const sender = Keypair.generate();
+    const recipients = [Keypair.generate(), Keypair.generate()].map((kp, i) => ({
+      to: kp.publicKey.toString(),
+      lamports: 1000 * (i + 1),
+    }));
+
+    const res = await axios.post(`${HTTP_URL}/send/sol/batch`, {
+      from: sender.publicKey.toString(),
+      recipients,
+    });
+
+    expect(res.status).toBe(SUCCESS_CODE);
+    expect(res.data.data.instructions.length).toBe(2);
+    expect(res.data.data.total_lamports).toBe(3000);
+    expect(res.data.data.required_signers).toEqual([sender.publicKey.toString()]);
+    res.data.data.instructions.forEach((ix, i) => {
+      expect(ix.program_id).toBe("11111111111111111111111111111111");
+      expect(ix.accounts[0].pubkey).toBe(sender.publicKey.toString());
+      expect(ix.accounts[0].is_signer).toBe(true);
+      expect(ix.accounts[1].pubkey).toBe(recipients[i].to);
+    });
+  });
+
+  test("POST /send/sol/batch should name the invalid recipient entry", async () => {
+    const res = await axios.post(`${HTTP_URL}/send/sol/batch`, {
+      from: Keypair.generate().publicKey.toString(),
+      recipients: [
+        { to: Keypair.generate().publicKey.toString(), lamports: 1000 },
+        { to: "not-a-key", lamports: 1000 },
+      ],
+    }, {
+      validateStatus: () => true // Don't throw on any status code
+    });
+
+    expect(res.status).toBe(ERROR_CODE);
+    expect(res.data.error).toBe("recipients[1]: Invalid recipient public key");
+  });
+
+  testIf(SAFE_MODE_MAINNET)("POST /send/sol should require confirmMainnet in safe mode", async () => {
+    const body = {
+      from: Keypair.generate().publicKey.toString(),
+      to: Keypair.generate().publicKey.toString(),
+      lamports: 1000,
+    };
+
+    const blocked = await axios.post(`${HTTP_URL}/send/sol`, body, {
+      validateStatus: () => true // Don't throw on any status code
+    });
+    expect(blocked.status).toBe(ERROR_CODE);
+    expect(blocked.data.error).toContain("confirmMainnet");
+
+    const confirmed = await axios.post(`${HTTP_URL}/send/sol`, { ...body, confirmMainnet: true });
+    expect(confirmed.status).toBe(SUCCESS_CODE);
+    expect(confirmed.data.success).toBe(true);
+  });
+
+  test("POST /keypair/sign-and-build should return a signed transaction that verifies", async () => {
+    const recipient = Keypair.generate();
+    const recentBlockhash = Keypair.generate().publicKey.toString();
+
+    const res = await axios.post(`${HTTP_URL}/keypair/sign-and-build`, {
+      instruction: {
+        type: "sendSol",
+        params: {
+          from: generatedKeypair.pubkey,
+          to: recipient.publicKey.toString(),
+          lamports: 1000,
+        },
+      },
+      secret: generatedKeypair.secret,
+      recentBlockhash,
+    });
+
+    expect(res.status).toBe(SUCCESS_CODE);
+    expect(res.data.data.signer).toBe(generatedKeypair.pubkey);
+    expect(res.data.data.recent_blockhash).toBe(recentBlockhash);
+
+    const tx = Transaction.from(Buffer.from(res.data.data.transaction, "base64"));
+    expect(tx.verifySignatures()).toBe(true);
+    expect(tx.feePayer.toString()).toBe(generatedKeypair.pubkey);
+    expect(bs58.encode(tx.signature)).toBe(res.data.data.signature);
+  });
+
+  test("POST /keypair/sign-and-build should reject a secret that is not the required signer", async () => {
+    const other = Keypair.generate();
+
+    const res = await axios.post(`${HTTP_URL}/keypair/sign-and-build`, {
+      instruction: {
+        type: "sendSol",
+        params: {
+          from: Keypair.generate().publicKey.toString(),
+          to: Keypair.generate().publicKey.toString(),
+          lamports: 1000,
+        },
+      },
+      secret: bs58.encode(other.secretKey),
+      recentBlockhash: Keypair.generate().publicKey.toString(),
+    }, {
+      validateStatus: () => true // Don't throw on any status code
+    });
+
+    expect(res.status).toBe(ERROR_CODE);
+    expect(res.data.success).toBe(false);
+  });
+
+  test("POST /token/mint?format=anchor should key accounts by role", async () => {
+    const mintKeypair = Keypair.generate();
+    const userKeypair = Keypair.generate();
+
+    const res = await axios.post(`${HTTP_URL}/token/mint?format=anchor`, {
+      mint: mintKeypair.publicKey.toString(),
+      destination: userKeypair.publicKey.toString(),
+      authority: generatedKeypair.pubkey,
+      amount: 1000,
+    });
+
+    const ata = await getAssociatedTokenAddress(mintKeypair.publicKey, userKeypair.publicKey);
+    expect(res.status).toBe(SUCCESS_CODE);
+    expect(Array.isArray(res.data.data.accounts)).toBe(false);
+    expect(res.data.data.accounts.mint.pubkey).toBe(mintKeypair.publicKey.toString());
+    expect(res.data.data.accounts.destination.pubkey).toBe(ata.toString());
+    expect(res.data.data.accounts.mintAuthority.pubkey).toBe(generatedKeypair.pubkey);
+    expect(res.data.data.accounts.mintAuthority.is_signer).toBe(true);
+  });
+
+  test("GET /cluster/epoch should return 504 when the client deadline elapses", async () => {
+    // A 1 ms budget is far shorter than any RPC round-trip.
+    const res = await axios.get(`${HTTP_URL}/cluster/epoch`, {
+      headers: { "X-Request-Timeout-Ms": "1" },
+      validateStatus: () => true // Don't throw on any status code
+    });
+
+    expect(res.status).toBe(504);
+    expect(res.data.success).toBe(false);
+    expect(res.data.error).toBeDefined();
+  });
+
+  test("POST /token/wrap should return create, transfer, and sync_native in order", async () => {
+    const owner = Keypair.generate();
+    const nativeMint = new PublicKey("So11111111111111111111111111111111111111112");
+    const wsolAta = await getAssociatedTokenAddress(nativeMint, owner.publicKey);
+
+    const res = await axios.post(`${HTTP_URL}/token/wrap`, {
+      owner: owner.publicKey.toString(),
+      lamports: 1000000,
+    });
+
+    expect(res.status).toBe(SUCCESS_CODE);
+    expect(res.data.data.wsol_account).toBe(wsolAta.toString());
+    const [create, transfer, sync] = res.data.data.instructions;
+    expect(create.program_id).toBe("ATokenGPvbdGVxr1b2hvZbsiqW5xWH25efTNsLJA8knL");
+    expect(transfer.program_id).toBe("11111111111111111111111111111111");
+    expect(transfer.accounts[1].pubkey).toBe(wsolAta.toString());
+    expect(sync.program_id).toBe(TOKEN_PROGRAM_ID);
+    expect(sync.accounts[0].pubkey).toBe(wsolAta.toString());
+  });
 });